@@ -0,0 +1,742 @@
+use std::collections::HashMap;
+
+use image::imageops::FilterType;
+use image::GenericImageView;
+
+use crate::color::{color_distance, from_linear, luminance, to_linear, ColorDistance};
+use crate::{DynamicImage, RenderOptions};
+
+/// Rectangles are written as X,Y,WIDTH,HEIGHT in pixels
+pub fn parse_crop(crop: &str) -> Option<(u32, u32, u32, u32)> {
+    let values: Vec<u32> = crop.split(',').map(|value| value.trim().parse().ok()).collect::<Option<_>>()?;
+    match values[..] {
+        [x, y, width, height] if width > 0 && height > 0 => Some((x, y, width, height)),
+        _ => None
+    }
+}
+
+pub(crate) fn crop(img: &DynamicImage, (x, y, width, height): (u32, u32, u32, u32)) -> Result<DynamicImage, String> {
+    let (image_width, image_height) = img.dimensions();
+    if u64::from(x) + u64::from(width) > u64::from(image_width) || u64::from(y) + u64::from(height) > u64::from(image_height) {
+        return Err(format!("Crop {},{},{},{} is outside the {}x{} image", x, y, width, height, image_width, image_height));
+    }
+    Ok(img.crop_imm(x, y, width, height))
+}
+
+/// The part of an image left after trimming the rows and columns around its edges that are fully transparent, or
+/// all color when it's given, as (x, y, width, height). None if every pixel would be trimmed
+pub fn trim_bounds(img: &DynamicImage, color: Option<[u8; 3]>) -> Option<(u32, u32, u32, u32)> {
+    let img = img.to_rgba8();
+    let border = |pixel: &image::Rgba<u8>| match color {
+        Some(color) => pixel[0] == color[0] && pixel[1] == color[1] && pixel[2] == color[2],
+        None => pixel[3] == 0
+    };
+
+    // (left, top, right, bottom) of the pixels that aren't border, inclusive
+    let mut bounds: Option<(u32, u32, u32, u32)> = None;
+    for (x, y, _) in img.enumerate_pixels().filter(|(_, _, pixel)| !border(pixel)) {
+        bounds = Some(match bounds {
+            Some((left, top, right, bottom)) => (left.min(x), top.min(y), right.max(x), bottom.max(y)),
+            None => (x, y, x, y)
+        });
+    }
+    bounds.map(|(left, top, right, bottom)| (left, top, right - left + 1, bottom - top + 1))
+}
+
+/// Unsharp masks are written as AMOUNT,THRESHOLD. amount is how much of the difference from a blurred copy is added
+/// back, so how much harder edges get, and threshold is the smallest difference that gets sharpened
+pub fn parse_sharpen(sharpen: &str) -> Option<(f32, i32)> {
+    let mut values = sharpen.split(',').map(str::trim);
+    let amount: f32 = values.next()?.parse().ok()?;
+    let threshold: i32 = values.next()?.parse().ok()?;
+    if values.next().is_some() || amount <= 0.0 || !amount.is_finite() || !(0..=255).contains(&threshold) {
+        return None;
+    }
+    Some((amount, threshold))
+}
+
+/// Degrees clockwise an image can be rotated by
+pub static ROTATIONS: [&str; 3] = [
+    "90",
+    "180",
+    "270"
+];
+
+fn rotate(img: &DynamicImage, degrees: u32) -> DynamicImage {
+    match degrees {
+        90 => img.rotate90(),
+        180 => img.rotate180(),
+        270 => img.rotate270(),
+        _ => img.clone()
+    }
+}
+
+// replaces each pixel with its luminance like ascii and braille modes see it
+fn grayscale(img: &DynamicImage) -> DynamicImage {
+    let mut img = img.to_rgba8();
+    for pixel in img.pixels_mut() {
+        let level = luminance([pixel[0], pixel[1], pixel[2]]).round() as u8;
+        *pixel = image::Rgba([level, level, level, pixel[3]]);
+    }
+    DynamicImage::ImageRgba8(img)
+}
+
+// turns each color around the HSV color wheel keeping its saturation and value. image's huerotate keeps
+// luminance instead which turns pure red into a dark green
+fn rotate_hue(img: &DynamicImage, degrees: i32) -> DynamicImage {
+    let mut img = img.to_rgba8();
+    for pixel in img.pixels_mut() {
+        let (r, g, b) = (f32::from(pixel[0]), f32::from(pixel[1]), f32::from(pixel[2]));
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let chroma = max - min;
+
+        // grays have no hue
+        if chroma == 0.0 {
+            continue;
+        }
+
+        // sixths of the way around the wheel starting from red
+        let hue = if max == r {
+            ((g - b) / chroma).rem_euclid(6.0)
+        } else if max == g {
+            (b - r) / chroma + 2.0
+        } else {
+            (r - g) / chroma + 4.0
+        };
+        let hue = (hue + degrees as f32 / 60.0).rem_euclid(6.0);
+
+        let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+        let (r, g, b) = match hue as u32 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x)
+        };
+        *pixel = image::Rgba([(r + min).round() as u8, (g + min).round() as u8, (b + min).round() as u8, pixel[3]]);
+    }
+    DynamicImage::ImageRgba8(img)
+}
+
+// classic sepia tone matrix. rows are the output channels
+static SEPIA: [[f32; 3]; 3] = [
+    [0.393, 0.769, 0.189],
+    [0.349, 0.686, 0.168],
+    [0.272, 0.534, 0.131]
+];
+
+fn sepia(img: &DynamicImage) -> DynamicImage {
+    let mut img = img.to_rgba8();
+    for pixel in img.pixels_mut() {
+        let color = [f32::from(pixel[0]), f32::from(pixel[1]), f32::from(pixel[2])];
+        for i in 0..3 {
+            let value = SEPIA[i][0] * color[0] + SEPIA[i][1] * color[1] + SEPIA[i][2] * color[2];
+            pixel[i] = value.round().min(255.0) as u8;
+        }
+    }
+    DynamicImage::ImageRgba8(img)
+}
+
+// out = 255 * (in / 255) ^ (1 / gamma) for each color channel
+fn gamma_correct(img: &DynamicImage, gamma: f32) -> DynamicImage {
+    let table: Vec<u8> = (0..=255).map(|i| (255.0 * (i as f32 / 255.0).powf(1.0 / gamma)).round() as u8).collect();
+
+    let mut img = img.to_rgba8();
+    for pixel in img.pixels_mut() {
+        for i in 0..3 {
+            pixel[i] = table[usize::from(pixel[i])];
+        }
+    }
+    DynamicImage::ImageRgba8(img)
+}
+
+// sigma of the blur that finds edges. images are sharpened after they're resized so this is in cells
+static SHARPEN_SIGMA: f32 = 1.0;
+
+// adds amount times the difference between each channel and a blurred copy when it's above threshold. image's
+// unsharpen adds the absolute difference, which brightens the dark side of edges instead of darkening it
+fn unsharpen(img: &DynamicImage, amount: f32, threshold: i32) -> DynamicImage {
+    let mut img = img.to_rgba8();
+    let blurred = image::imageops::blur(&img, SHARPEN_SIGMA);
+
+    for (pixel, blurred) in img.pixels_mut().zip(blurred.pixels()) {
+        for i in 0..3 {
+            let difference = i32::from(pixel[i]) - i32::from(blurred[i]);
+            if difference.abs() > threshold {
+                pixel[i] = (f32::from(pixel[i]) + amount * difference as f32).round().clamp(0.0, 255.0) as u8;
+            }
+        }
+    }
+    DynamicImage::ImageRgba8(img)
+}
+
+// sobel operator. each pixel becomes the size of the change in luminance around it, with pixels past the edges
+// taken from the nearest edge
+fn edges(img: &DynamicImage) -> DynamicImage {
+    let img = img.to_rgba8();
+    let (width, height) = img.dimensions();
+    let gray: Vec<f32> = img.pixels().map(|pixel| luminance([pixel[0], pixel[1], pixel[2]])).collect();
+
+    let at = |x: i64, y: i64| {
+        let x = x.clamp(0, i64::from(width) - 1) as usize;
+        let y = y.clamp(0, i64::from(height) - 1) as usize;
+        gray[y * width as usize + x]
+    };
+
+    DynamicImage::ImageRgba8(image::RgbaImage::from_fn(width, height, |x, y| {
+        let (x, y) = (i64::from(x), i64::from(y));
+        let horizontal = at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1)
+            - at(x - 1, y - 1) - 2.0 * at(x - 1, y) - at(x - 1, y + 1);
+        let vertical = at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1)
+            - at(x - 1, y - 1) - 2.0 * at(x, y - 1) - at(x + 1, y - 1);
+        let value = horizontal.hypot(vertical).round().min(255.0) as u8;
+        image::Rgba([value, value, value, img.get_pixel(x as u32, y as u32)[3]])
+    }))
+}
+
+// pixels at least as bright as level become white and the rest become black
+fn binarize(img: &DynamicImage, level: u8) -> DynamicImage {
+    let mut img = img.to_rgba8();
+    for pixel in img.pixels_mut() {
+        let value = if luminance([pixel[0], pixel[1], pixel[2]]) >= f32::from(level) { 255 } else { 0 };
+        pixel[0] = value;
+        pixel[1] = value;
+        pixel[2] = value;
+    }
+    DynamicImage::ImageRgba8(img)
+}
+
+// median cut. the box of colors that spans the widest range of a channel is split in half at its median until there
+// are enough boxes, and each box becomes the average of its colors
+fn median_cut(mut colors: Vec<[u8; 3]>, count: usize) -> Vec<[u8; 3]> {
+    let range = |colors: &[[u8; 3]], i: usize| {
+        let values = colors.iter().map(|color| color[i]);
+        values.clone().max().unwrap_or(0) - values.min().unwrap_or(0)
+    };
+
+    colors.sort_unstable();
+    colors.dedup();
+    let mut boxes = vec![colors];
+
+    while boxes.len() < count {
+        // boxes with one color can't be split
+        let widest = boxes.iter().enumerate()
+            .filter(|(_, colors)| colors.len() > 1)
+            .flat_map(|(index, colors)| (0..3).map(move |i| (index, i, range(colors, i))))
+            .max_by_key(|&(_, _, range)| range);
+
+        let (index, channel) = match widest {
+            Some((index, channel, _)) => (index, channel),
+            None => break
+        };
+
+        let mut colors = boxes.swap_remove(index);
+        colors.sort_unstable_by_key(|color| color[channel]);
+        let upper = colors.split_off(colors.len() / 2);
+        boxes.push(colors);
+        boxes.push(upper);
+    }
+
+    boxes.iter().filter(|colors| !colors.is_empty()).map(|colors| {
+        let mut sum = [0; 3];
+        for color in colors {
+            for i in 0..3 {
+                sum[i] += u32::from(color[i]);
+            }
+        }
+        let count = colors.len() as u32;
+        [(sum[0] + count / 2) / count, (sum[1] + count / 2) / count, (sum[2] + count / 2) / count].map(|c| c as u8)
+    }).collect()
+}
+
+// reduces the image to a palette of at most count colors picked from the colors in it
+fn quantize(img: &DynamicImage, count: u32, distance: ColorDistance) -> DynamicImage {
+    let mut img = img.to_rgba8();
+    let palette = median_cut(img.pixels().map(|pixel| [pixel[0], pixel[1], pixel[2]]).collect(), count as usize);
+
+    // images have far fewer colors than pixels so the closest palette color is only searched for once per color
+    let mut closest = HashMap::new();
+    for pixel in img.pixels_mut() {
+        let color = [pixel[0], pixel[1], pixel[2]];
+        let new = *closest.entry(color).or_insert_with(|| {
+            *palette.iter().min_by_key(|&&entry| color_distance(color, entry, distance)).unwrap()
+        });
+        pixel[0] = new[0];
+        pixel[1] = new[1];
+        pixel[2] = new[2];
+    }
+    DynamicImage::ImageRgba8(img)
+}
+
+// rounds of k-means used to refine the palette from median cut
+static PALETTE_ROUNDS: usize = 8;
+
+// the count most common colors in the opaque pixels of an image and how many pixels are closest to each, most
+// common first
+pub(crate) fn palette(img: &DynamicImage, count: u32, distance: ColorDistance) -> Vec<([u8; 3], usize)> {
+    let mut counts = HashMap::new();
+    for pixel in img.to_rgba8().pixels().filter(|pixel| pixel[3] > 0) {
+        *counts.entry([pixel[0], pixel[1], pixel[2]]).or_insert(0) += 1;
+    }
+
+    // median cut weighs a color used once the same as one used everywhere, so k-means moves each color to the
+    // average of the pixels closest to it
+    let mut palette = median_cut(counts.keys().copied().collect(), count as usize);
+    let mut totals = Vec::new();
+    for _ in 0..PALETTE_ROUNDS {
+        let mut sums = vec![([0; 3], 0); palette.len()];
+        for (&color, &pixels) in &counts {
+            let closest = (0..palette.len()).min_by_key(|&i| color_distance(color, palette[i], distance)).unwrap();
+            for (sum, &value) in sums[closest].0.iter_mut().zip(&color) {
+                *sum += u64::from(value) * pixels as u64;
+            }
+            sums[closest].1 += pixels;
+        }
+
+        let next: Vec<_> = sums.iter().filter(|&&(_, pixels)| pixels > 0).map(|&(sum, pixels)| {
+            let average = |i: usize| (sum[i] as f64 / pixels as f64).round() as u8;
+            [average(0), average(1), average(2)]
+        }).collect();
+        totals = sums.into_iter().map(|(_, pixels)| pixels).filter(|&pixels| pixels > 0).collect();
+        if next == palette {
+            break;
+        }
+        palette = next;
+    }
+
+    let mut palette: Vec<_> = palette.into_iter().zip(totals).collect();
+    palette.sort_by_key(|&(_, pixels)| std::cmp::Reverse(pixels));
+    palette
+}
+
+// resizes in linear light. averaging sRGB values darkens the result because they're brighter than they're stored
+pub(crate) fn resize_linear(img: &DynamicImage, width: u32, height: u32, filter: FilterType) -> DynamicImage {
+    // 16 bits keeps the dark values that linear light crowds together apart
+    let decode: Vec<u16> = (0..=u16::MAX).map(|value| (to_linear(f32::from(value) / 65535.0) * 65535.0).round() as u16)
+        .collect();
+    let encode: Vec<u8> = (0..=u16::MAX).map(|value| (from_linear(f32::from(value) / 65535.0) * 255.0).round() as u8)
+        .collect();
+
+    let mut linear = img.to_rgba16();
+    for pixel in linear.pixels_mut() {
+        for channel in pixel.0.iter_mut().take(3) {
+            *channel = decode[usize::from(*channel)];
+        }
+    }
+
+    let resized = image::imageops::resize(&linear, width, height, filter);
+    DynamicImage::ImageRgba8(image::RgbaImage::from_fn(width, height, |x, y| {
+        let pixel = resized.get_pixel(x, y);
+        let alpha = ((u32::from(pixel[3]) * 255 + 32767) / 65535) as u8;
+        let channel = |i: usize| encode[usize::from(pixel[i])];
+        image::Rgba([channel(0), channel(1), channel(2), alpha])
+    }))
+}
+
+// draws top over img, stretched to the same size, with its alpha scaled by opacity
+pub(crate) fn overlay(img: &DynamicImage, top: &DynamicImage, opacity: f32, filter: FilterType) -> DynamicImage {
+    let mut img = img.to_rgba8();
+    let top = top.resize_exact(img.width(), img.height(), filter).to_rgba8();
+    blend(&mut img, &top, opacity);
+    DynamicImage::ImageRgba8(img)
+}
+
+// draws img over backdrop, stretched to the same size as img
+pub(crate) fn underlay(img: &DynamicImage, backdrop: &DynamicImage, filter: FilterType) -> DynamicImage {
+    let mut backdrop = backdrop.resize_exact(img.width(), img.height(), filter).to_rgba8();
+    blend(&mut backdrop, &img.to_rgba8(), 1.0);
+    DynamicImage::ImageRgba8(backdrop)
+}
+
+// draws top over img with the source over operator. both are the same size
+fn blend(img: &mut image::RgbaImage, top: &image::RgbaImage, opacity: f32) {
+    for (pixel, top) in img.pixels_mut().zip(top.pixels()) {
+        let alpha = f32::from(top[3]) / 255.0 * opacity;
+        let below = f32::from(pixel[3]) / 255.0 * (1.0 - alpha);
+        let total = alpha + below;
+        if total == 0.0 {
+            continue;
+        }
+
+        for i in 0..3 {
+            pixel[i] = ((f32::from(top[i]) * alpha + f32::from(pixel[i]) * below) / total).round() as u8;
+        }
+        pixel[3] = (total * 255.0).round() as u8;
+    }
+}
+
+// pixels that are different in other are red, brighter the bigger the difference, and the rest are dim gray
+pub(crate) fn diff(img: &DynamicImage, other: &DynamicImage) -> DynamicImage {
+    let (img, other) = (img.to_rgba8(), other.to_rgba8());
+
+    DynamicImage::ImageRgba8(image::RgbaImage::from_fn(img.width(), img.height(), |x, y| {
+        let (a, b) = (img.get_pixel(x, y), other.get_pixel(x, y));
+        let difference = (0..4).map(|i| (i16::from(a[i]) - i16::from(b[i])).unsigned_abs()).max().unwrap();
+        if difference == 0 {
+            let dim = (luminance([a[0], a[1], a[2]]) / 4.0).round() as u8;
+            image::Rgba([dim, dim, dim, 255])
+        } else {
+            image::Rgba([(128 + difference / 2) as u8, 0, 0, 255])
+        }
+    }))
+}
+
+// changes made to the colors of the decoded image before it's resized. None if nothing changes
+pub(crate) fn before_resize(img: &DynamicImage, options: &RenderOptions) -> Option<DynamicImage> {
+    let mut adjusted = None;
+
+    // flip before rotating so --flip-h always mirrors the image as it is stored
+    if options.flip_h {
+        adjusted = Some(adjusted.as_ref().unwrap_or(img).fliph());
+    }
+
+    if options.flip_v {
+        adjusted = Some(adjusted.as_ref().unwrap_or(img).flipv());
+    }
+
+    if options.rotate != 0 {
+        adjusted = Some(rotate(adjusted.as_ref().unwrap_or(img), options.rotate));
+    }
+
+    if options.grayscale {
+        adjusted = Some(grayscale(adjusted.as_ref().unwrap_or(img)));
+    }
+
+    if options.brightness != 0 {
+        adjusted = Some(adjusted.as_ref().unwrap_or(img).brighten(options.brightness));
+    }
+
+    if options.contrast != 0.0 {
+        adjusted = Some(adjusted.as_ref().unwrap_or(img).adjust_contrast(options.contrast));
+    }
+
+    if options.hue.rem_euclid(360) != 0 {
+        adjusted = Some(rotate_hue(adjusted.as_ref().unwrap_or(img), options.hue.rem_euclid(360)));
+    }
+
+    // alpha is left alone
+    if options.invert {
+        let mut inverted = adjusted.unwrap_or_else(|| img.clone());
+        inverted.invert();
+        adjusted = Some(inverted);
+    }
+
+    if let Some(sigma) = options.blur {
+        adjusted = Some(adjusted.as_ref().unwrap_or(img).blur(sigma));
+    }
+
+    adjusted
+}
+
+// changes made to the colors of the resized image. None if nothing changes
+pub(crate) fn after_resize(img: &DynamicImage, options: &RenderOptions) -> Option<DynamicImage> {
+    let mut adjusted = None;
+
+    if options.sepia {
+        adjusted = Some(sepia(adjusted.as_ref().unwrap_or(img)));
+    }
+
+    if options.gamma != 1.0 {
+        adjusted = Some(gamma_correct(adjusted.as_ref().unwrap_or(img), options.gamma));
+    }
+
+    if let Some((amount, threshold)) = options.sharpen {
+        adjusted = Some(unsharpen(adjusted.as_ref().unwrap_or(img), amount, threshold));
+    }
+
+    if options.edges {
+        adjusted = Some(edges(adjusted.as_ref().unwrap_or(img)));
+    }
+
+    if let Some(level) = options.binarize {
+        adjusted = Some(binarize(adjusted.as_ref().unwrap_or(img), level));
+    }
+
+    if let Some(count) = options.quantize {
+        adjusted = Some(quantize(adjusted.as_ref().unwrap_or(img), count, options.color_distance));
+    }
+
+    adjusted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(width: u32, height: u32, color: [u8; 4]) -> DynamicImage {
+        DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(width, height, image::Rgba(color)))
+    }
+
+    // the top left pixel after the adjustments made before resizing
+    fn adjusted(img: &DynamicImage, options: RenderOptions) -> [u8; 4] {
+        before_resize(img, &options).unwrap().to_rgba8().get_pixel(0, 0).0
+    }
+
+    #[test]
+    fn sharpening_steepens_edges() {
+        // an edge from dark to light gray between the 8th and 9th pixels
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::from_fn(16, 1, |x, _| {
+            let level = if x < 8 { 64 } else { 192 };
+            image::Rgba([level, level, level, 255])
+        }));
+        let sharpened = unsharpen(&img, 1.0, 0);
+
+        let contrast = |img: &DynamicImage| {
+            let img = img.to_rgba8();
+            i32::from(img.get_pixel(8, 0)[0]) - i32::from(img.get_pixel(7, 0)[0])
+        };
+        assert!(contrast(&sharpened) > contrast(&img));
+        // flat areas away from the edge are left alone
+        assert_eq!(sharpened.to_rgba8().get_pixel(0, 0), img.to_rgba8().get_pixel(0, 0));
+
+        // bigger amounts sharpen harder and a high threshold leaves the edge alone
+        assert!(contrast(&unsharpen(&img, 2.0, 0)) > contrast(&sharpened));
+        assert!(contrast(&unsharpen(&img, 0.5, 0)) < contrast(&sharpened));
+        assert_eq!(unsharpen(&img, 1.0, 255).to_bytes(), img.to_rgba8().into_raw());
+    }
+
+    #[test]
+    fn sharpen_values() {
+        assert_eq!(parse_sharpen("1,0"), Some((1.0, 0)));
+        assert_eq!(parse_sharpen("2.5, 10"), Some((2.5, 10)));
+        assert_eq!(parse_sharpen("0,0"), None);
+        assert_eq!(parse_sharpen("1,256"), None);
+        assert_eq!(parse_sharpen("1"), None);
+    }
+
+    #[test]
+    fn grayscale_uses_luminance() {
+        let options = RenderOptions { grayscale: true, ..RenderOptions::default() };
+        assert_eq!(adjusted(&solid(1, 1, [255, 0, 0, 255]), options.clone()), [76, 76, 76, 255]);
+
+        let mut out = Vec::new();
+        crate::render_image(&solid(2, 2, [255, 0, 0, 255]), &options, &mut out).unwrap();
+        assert!(String::from_utf8(out).unwrap().contains("\x1b[48;2;76;76;76m"));
+    }
+
+    #[test]
+    fn brightness_raises_channels() {
+        let options = RenderOptions { brightness: 50, ..RenderOptions::default() };
+        assert_eq!(adjusted(&solid(1, 1, [128, 128, 128, 255]), options.clone()), [178, 178, 178, 255]);
+        // saturates instead of wrapping around
+        assert_eq!(adjusted(&solid(1, 1, [230, 230, 230, 255]), options), [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn gamma_brightens_midtones() {
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::from_fn(3, 1, |x, _| {
+            let level = [0, 128, 255][x as usize];
+            image::Rgba([level, level, level, 255])
+        }));
+        let options = RenderOptions { gamma: 2.2, ..RenderOptions::default() };
+        let corrected = after_resize(&img, &options).unwrap().to_rgba8();
+
+        assert_eq!(corrected.get_pixel(0, 0).0, [0, 0, 0, 255]);
+        assert_eq!(corrected.get_pixel(1, 0).0, [186, 186, 186, 255]);
+        assert_eq!(corrected.get_pixel(2, 0).0, [255, 255, 255, 255]);
+        assert!(after_resize(&img, &RenderOptions::default()).is_none());
+    }
+
+    #[test]
+    fn invert_keeps_alpha() {
+        let options = RenderOptions { invert: true, ..RenderOptions::default() };
+        assert_eq!(adjusted(&solid(1, 1, [0, 0, 0, 100]), options.clone()), [255, 255, 255, 100]);
+
+        let mut out = Vec::new();
+        crate::render_image(&solid(4, 4, [0, 0, 0, 255]), &options, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("48;2;"));
+        assert!(out.split("48;2;").skip(1).all(|escape| escape.starts_with("255;255;255m")));
+    }
+
+    #[test]
+    fn sepia_of_white() {
+        let options = RenderOptions { sepia: true, ..RenderOptions::default() };
+        let toned = after_resize(&solid(1, 1, [255, 255, 255, 255]), &options).unwrap();
+        // the red and green rows add up to more than 1 so they're clamped
+        assert_eq!(toned.to_rgba8().get_pixel(0, 0).0, [255, 255, 239, 255]);
+    }
+
+    #[test]
+    fn hue_rotation() {
+        let red = solid(1, 1, [255, 0, 0, 255]);
+        assert_eq!(adjusted(&red, RenderOptions { hue: 120, ..RenderOptions::default() }), [0, 255, 0, 255]);
+        assert_eq!(adjusted(&red, RenderOptions { hue: 240, ..RenderOptions::default() }), [0, 0, 255, 255]);
+        // turning backwards is the same as the rest of the way around
+        assert_eq!(adjusted(&red, RenderOptions { hue: -240, ..RenderOptions::default() }), [0, 255, 0, 255]);
+        assert!(before_resize(&red, &RenderOptions { hue: 360, ..RenderOptions::default() }).is_none());
+    }
+
+    // each pixel has its own color so where it ends up can be checked
+    fn gradient(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgba8(image::RgbaImage::from_fn(width, height, |x, y| image::Rgba([x as u8, y as u8, 0, 255])))
+    }
+
+    #[test]
+    fn crops() {
+        let img = gradient(100, 100);
+        let cropped = crop(&img, parse_crop("10,10,20,20").unwrap()).unwrap();
+        assert_eq!(cropped.dimensions(), (20, 20));
+        assert_eq!(cropped.get_pixel(0, 0), img.get_pixel(10, 10));
+        assert_eq!(cropped.get_pixel(19, 19), img.get_pixel(29, 29));
+
+        assert!(crop(&img, (90, 0, 20, 20)).is_err());
+        assert!(crop(&img, (u32::MAX, 0, 1, 1)).is_err());
+        assert_eq!(parse_crop("1,2,0,4"), None);
+        assert_eq!(parse_crop("1,2,3"), None);
+    }
+
+    #[test]
+    fn rotations() {
+        let img = gradient(2, 3);
+        let rotated = before_resize(&img, &RenderOptions { rotate: 90, ..RenderOptions::default() }).unwrap();
+        assert_eq!(rotated.dimensions(), (3, 2));
+        // clockwise, so the left column becomes the top row read from the bottom up
+        for (x, y, pixel) in img.pixels() {
+            assert_eq!(rotated.get_pixel(2 - y, x), pixel);
+        }
+
+        let upside_down = before_resize(&img, &RenderOptions { rotate: 180, ..RenderOptions::default() }).unwrap();
+        assert_eq!(upside_down.get_pixel(0, 0), img.get_pixel(1, 2));
+        let rotated = before_resize(&img, &RenderOptions { rotate: 270, ..RenderOptions::default() }).unwrap();
+        assert_eq!(rotated.get_pixel(0, 0), img.get_pixel(1, 0));
+    }
+
+    #[test]
+    fn flips() {
+        let img = gradient(4, 3);
+        let mirrored = before_resize(&img, &RenderOptions { flip_h: true, ..RenderOptions::default() }).unwrap();
+        let flipped = before_resize(&img, &RenderOptions { flip_v: true, ..RenderOptions::default() }).unwrap();
+        for (x, y, pixel) in img.pixels() {
+            assert_eq!(mirrored.get_pixel(3 - x, y), pixel);
+            assert_eq!(flipped.get_pixel(x, 2 - y), pixel);
+        }
+
+        // both is the same as turning it upside down, and flipping happens before rotating
+        let both = RenderOptions { flip_h: true, flip_v: true, ..RenderOptions::default() };
+        assert_eq!(before_resize(&img, &both).unwrap().to_bytes(), img.rotate180().to_bytes());
+        let rotated = before_resize(&img, &RenderOptions { flip_h: true, rotate: 90, ..RenderOptions::default() }).unwrap();
+        assert_eq!(rotated.to_bytes(), img.fliph().rotate90().to_bytes());
+    }
+
+    // the different colors in an image
+    fn colors(img: &DynamicImage) -> std::collections::HashSet<[u8; 3]> {
+        img.to_rgb8().pixels().map(|pixel| pixel.0).collect()
+    }
+
+    #[test]
+    fn quantize_limits_colors() {
+        let img = DynamicImage::ImageRgb8(image::RgbImage::from_fn(40, 25, |x, y| image::Rgb([(x * 6) as u8, (y * 10) as u8, 128])));
+        assert_eq!(colors(&img).len(), 1000);
+
+        let quantized = after_resize(&img, &RenderOptions { quantize: Some(4), ..RenderOptions::default() }).unwrap();
+        assert!(colors(&quantized).len() <= 4);
+        assert!(colors(&quantized).len() > 1);
+    }
+
+    #[test]
+    fn edges_outline_squares() {
+        // a black square from 3,3 to 6,6 on white
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::from_fn(10, 10, |x, y| {
+            let level = if (3..7).contains(&x) && (3..7).contains(&y) { 0 } else { 255 };
+            image::Rgba([level, level, level, 255])
+        }));
+        let outlined = after_resize(&img, &RenderOptions { edges: true, ..RenderOptions::default() }).unwrap().to_rgba8();
+
+        for &(x, y) in &[(3, 3), (2, 4), (6, 5), (4, 7)] {
+            assert!(outlined.get_pixel(x, y)[0] > 128, "{},{}", x, y);
+        }
+        // inside the square, away from it, and at the edges of the image nothing changes
+        for &(x, y) in &[(4, 4), (5, 5), (0, 0), (9, 9), (0, 5)] {
+            assert_eq!(outlined.get_pixel(x, y).0, [0, 0, 0, 255], "{},{}", x, y);
+        }
+    }
+
+    #[test]
+    fn blur_spreads_pixels() {
+        let mut img = image::RgbaImage::from_pixel(5, 5, image::Rgba([0, 0, 0, 255]));
+        img.put_pixel(2, 2, image::Rgba([255, 255, 255, 255]));
+        let options = RenderOptions { blur: Some(1.0), ..RenderOptions::default() };
+        let blurred = before_resize(&DynamicImage::ImageRgba8(img), &options).unwrap().to_rgba8();
+
+        let center = blurred.get_pixel(2, 2)[0];
+        assert!(center < 255);
+        for &(x, y) in &[(1, 2), (3, 2), (2, 1), (2, 3)] {
+            let neighbor = blurred.get_pixel(x, y)[0];
+            assert!(neighbor > 0 && neighbor < center, "{},{} is {}", x, y, neighbor);
+        }
+    }
+
+    #[test]
+    fn binarize_levels() {
+        let gray = solid(2, 2, [128, 128, 128, 255]);
+        let binarize = |level| after_resize(&gray, &RenderOptions { binarize: Some(level), ..RenderOptions::default() }).unwrap();
+
+        assert_eq!(colors(&binarize(100)), [[255; 3]].iter().copied().collect());
+        assert_eq!(colors(&binarize(128)), [[255; 3]].iter().copied().collect());
+        assert_eq!(colors(&binarize(129)), [[0; 3]].iter().copied().collect());
+    }
+
+    #[test]
+    fn diffs() {
+        let img = gradient(8, 8);
+        let same = diff(&img, &img);
+        // nothing is highlighted, just dimmed
+        assert!(same.to_rgb8().pixels().all(|pixel| pixel[0] == pixel[1] && pixel[1] == pixel[2] && pixel[0] <= 64));
+
+        let mut changed = img.to_rgba8();
+        changed.put_pixel(3, 5, image::Rgba([255, 255, 255, 255]));
+        let highlighted: Vec<_> = diff(&img, &DynamicImage::ImageRgba8(changed)).to_rgb8().enumerate_pixels()
+            .filter(|(_, _, pixel)| pixel[0] >= 128 && pixel[1] == 0)
+            .map(|(x, y, _)| (x, y))
+            .collect();
+        assert_eq!(highlighted, [(3, 5)]);
+    }
+
+    #[test]
+    fn overlay_opacity() {
+        let (base, top) = (gradient(6, 4), solid(3, 2, [0, 0, 255, 255]));
+        assert_eq!(overlay(&base, &top, 0.0, FilterType::Nearest), DynamicImage::ImageRgba8(base.to_rgba8()));
+        assert_eq!(overlay(&base, &top, 1.0, FilterType::Nearest), solid(6, 4, [0, 0, 255, 255]));
+    }
+
+    #[test]
+    fn trims() {
+        let mut img = image::RgbaImage::new(10, 10);
+        for y in 2..8 {
+            for x in 2..8 {
+                img.put_pixel(x, y, image::Rgba([255, 0, 0, 255]));
+            }
+        }
+        let img = DynamicImage::ImageRgba8(img);
+        assert_eq!(trim_bounds(&img, None), Some((2, 2, 6, 6)));
+        // a color trims its own pixels instead of the transparent ones
+        assert_eq!(trim_bounds(&img, Some([255, 0, 0])), Some((0, 0, 10, 10)));
+        assert_eq!(trim_bounds(&solid(10, 10, [255, 255, 255, 0]), None), None);
+    }
+
+    #[test]
+    fn linear_checkerboards_stay_bright() {
+        // averaging black and white pixels in linear light gives middle gray light, which is 188 in sRGB
+        let img = DynamicImage::ImageLuma8(image::GrayImage::from_fn(64, 64, |x, y| image::Luma([if (x + y) % 2 == 0 { 0 } else { 255 }])));
+        let mean = |img: DynamicImage| {
+            let img = img.to_luma8();
+            img.pixels().map(|pixel| f32::from(pixel[0])).sum::<f32>() / img.pixels().len() as f32
+        };
+
+        let linear = mean(resize_linear(&img, 8, 8, FilterType::Triangle));
+        assert!((183.0..193.0).contains(&linear), "{}", linear);
+        let naive = mean(img.resize_exact(8, 8, FilterType::Triangle));
+        assert!((123.0..133.0).contains(&naive), "{}", naive);
+    }
+
+    #[test]
+    fn underlays() {
+        let backdrop = solid(3, 3, [0, 0, 255, 255]);
+        // opaque pixels hide the backdrop and transparent ones show it
+        assert_eq!(underlay(&solid(6, 6, [255, 0, 0, 255]), &backdrop, FilterType::Nearest), solid(6, 6, [255, 0, 0, 255]));
+        assert_eq!(underlay(&solid(6, 6, [255, 0, 0, 0]), &backdrop, FilterType::Nearest), solid(6, 6, [0, 0, 255, 255]));
+    }
+}
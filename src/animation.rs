@@ -0,0 +1,299 @@
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::terminal::RawTty;
+
+// ctrl-c can only have one handler so it sends to whichever animation is playing
+static INTERRUPT: Mutex<Option<mpsc::Sender<Event>>> = Mutex::new(None);
+// whether the handler has been set. it stays false if setting it failed
+static HANDLER_SET: Mutex<bool> = Mutex::new(false);
+
+static HIDE_CURSOR: &[u8] = b"\x1b[?25l";
+static SHOW_CURSOR: &[u8] = b"\x1b[?25h";
+static RESET: &[u8] = b"\x1b[0m";
+
+// the exit code of a process killed by SIGINT
+static INTERRUPTED: i32 = 130;
+
+// how often the thread reading keys checks whether the animation is over
+static KEY_POLL: Duration = Duration::from_millis(50);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Key {
+    Pause,
+    Next,
+    Previous,
+    Quit
+}
+
+enum Event {
+    Interrupt,
+    Key(Key)
+}
+
+// space, the right and left arrows, and q. sequence is everything read since the last key
+fn key(sequence: &[u8]) -> Option<Key> {
+    match sequence {
+        b" " => Some(Key::Pause),
+        b"\x1b[C" => Some(Key::Next),
+        b"\x1b[D" => Some(Key::Previous),
+        b"q" => Some(Key::Quit),
+        _ => None
+    }
+}
+
+// which frame is showing and whether it's paused, kept apart from the terminal
+struct Player {
+    frames: usize,
+    frame: usize,
+    paused: bool,
+    plays: u32
+}
+
+impl Player {
+    fn new(frames: usize) -> Self {
+        Player { frames, frame: 0, paused: false, plays: 0 }
+    }
+
+    // moves on once the frame's delay is over. every time it gets back to the start the animation has played once
+    fn tick(&mut self) {
+        self.frame = (self.frame + 1) % self.frames;
+        if self.frame == 0 {
+            self.plays += 1;
+        }
+    }
+
+    // false when the key quits. the arrows only step while paused and stepping doesn't count as playing
+    fn press(&mut self, key: Key) -> bool {
+        match key {
+            Key::Pause => self.paused = !self.paused,
+            Key::Next if self.paused => self.frame = (self.frame + 1) % self.frames,
+            Key::Previous if self.paused => self.frame = (self.frame + self.frames - 1) % self.frames,
+            Key::Quit => return false,
+            _ => {}
+        }
+        true
+    }
+}
+
+// sends keys typed in the terminal until done is set
+fn read_keys(mut tty: RawTty, keys: mpsc::Sender<Event>, done: Arc<AtomicBool>) {
+    let mut sequence = Vec::new();
+    while !done.load(Ordering::Relaxed) {
+        let byte = match tty.read(KEY_POLL) {
+            Some(byte) => byte,
+            None => continue
+        };
+        sequence.push(byte);
+        // arrows are three bytes
+        if sequence == b"\x1b" || sequence == b"\x1b[" {
+            continue;
+        }
+        if let Some(key) = key(&sequence) {
+            let _ = keys.send(Event::Key(key));
+        }
+        sequence.clear();
+    }
+}
+
+// hides the cursor while frames are drawn and shows it again when dropped, even if drawing a frame fails
+struct HiddenCursor<'a, W: Write> {
+    out: &'a mut W,
+    shown: bool
+}
+
+impl<'a, W: Write> HiddenCursor<'a, W> {
+    fn hide(out: &'a mut W) -> std::io::Result<Self> {
+        out.write_all(HIDE_CURSOR)?;
+        Ok(HiddenCursor { out, shown: false })
+    }
+
+    // like dropping it but with the error
+    fn show(mut self) -> std::io::Result<()> {
+        self.shown = true;
+        self.out.write_all(RESET)?;
+        self.out.write_all(SHOW_CURSOR)?;
+        self.out.flush()
+    }
+}
+
+impl<W: Write> Drop for HiddenCursor<'_, W> {
+    fn drop(&mut self) {
+        if !self.shown {
+            let _ = self.out.write_all(RESET);
+            let _ = self.out.write_all(SHOW_CURSOR);
+            let _ = self.out.flush();
+        }
+    }
+}
+
+// draws frames until they've played loops times or an event stops them. true if they were stopped
+fn draw_frames(frames: &[(Vec<u8>, Duration)], loops: u32, events: &mpsc::Receiver<Event>, out: &mut impl Write)
+               -> std::io::Result<bool> {
+    let mut player = Player::new(frames.len());
+    let mut first = true;
+    while loops == 0 || player.plays < loops {
+        // move the cursor home to draw over the previous frame
+        if !first {
+            out.write_all(b"\x1b[H")?;
+        }
+        first = false;
+        let (frame, delay) = &frames[player.frame];
+        out.write_all(frame)?;
+        out.flush()?;
+
+        // frames are only interrupted between draws so the cursor is always left below the image
+        let event = if player.paused {
+            events.recv().ok()
+        } else {
+            events.recv_timeout(*delay).ok()
+        };
+        let stopped = match event {
+            Some(Event::Interrupt) => true,
+            Some(Event::Key(key)) => !player.press(key),
+            None => {
+                player.tick();
+                false
+            }
+        };
+        if stopped {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+// ctrl-c stops the animation that's playing. between animations it leaves the terminal the way it was and exits
+// like the default handler would
+fn set_handler() -> std::io::Result<()> {
+    let mut set = HANDLER_SET.lock().unwrap();
+    if *set {
+        return Ok(());
+    }
+
+    ctrlc::set_handler(|| {
+        match INTERRUPT.lock().unwrap().as_ref() {
+            Some(interrupt) => {
+                let _ = interrupt.send(Event::Interrupt);
+            }
+            None => {
+                let mut out = std::io::stdout();
+                let _ = out.write_all(RESET);
+                let _ = out.write_all(SHOW_CURSOR);
+                let _ = out.flush();
+                std::process::exit(INTERRUPTED);
+            }
+        }
+    }).map_err(std::io::Error::other)?;
+    *set = true;
+    Ok(())
+}
+
+/// Draws each frame over the last one until the animation has played `loops` times or ctrl-c is pressed.
+/// 0 loops plays forever. The cursor is hidden while it plays. When `interactive` is true and there's a terminal to
+/// read from, space pauses, the arrow keys step through frames while paused, and q quits. Returns true if ctrl-c or q
+/// was pressed
+pub fn play(frames: &[(Vec<u8>, Duration)], loops: u32, interactive: bool, out: &mut impl Write)
+            -> std::io::Result<bool> {
+    set_handler()?;
+    let cursor = HiddenCursor::hide(out)?;
+
+    let (sender, events) = mpsc::channel();
+    *INTERRUPT.lock().unwrap() = Some(sender.clone());
+
+    let done = Arc::new(AtomicBool::new(false));
+    let tty = if interactive { RawTty::open() } else { None };
+    let keys = match tty {
+        Some(tty) => {
+            let done = done.clone();
+            Some(thread::spawn(move || read_keys(tty, sender, done)))
+        }
+        None => None
+    };
+
+    let stopped = draw_frames(frames, loops, &events, cursor.out);
+
+    // ctrl-c after this exits instead of going to an animation that isn't playing
+    *INTERRUPT.lock().unwrap() = None;
+    // the terminal goes back to normal before anything else is drawn
+    done.store(true, Ordering::Relaxed);
+    if let Some(keys) = keys {
+        let _ = keys.join();
+    }
+
+    // ctrl-c only stops the loop so the cursor comes back either way
+    cursor.show()?;
+    stopped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys() {
+        assert_eq!(key(b" "), Some(Key::Pause));
+        assert_eq!(key(b"\x1b[C"), Some(Key::Next));
+        assert_eq!(key(b"\x1b[D"), Some(Key::Previous));
+        assert_eq!(key(b"q"), Some(Key::Quit));
+        assert_eq!(key(b"\x1b[A"), None);
+    }
+
+    #[test]
+    fn ticks_count_plays() {
+        let mut player = Player::new(3);
+        for frame in &[1, 2, 0, 1, 2, 0] {
+            player.tick();
+            assert_eq!(player.frame, *frame);
+        }
+        assert_eq!(player.plays, 2);
+    }
+
+    #[test]
+    fn arrows_step_while_paused() {
+        let mut player = Player::new(3);
+
+        // playing frames ignore the arrows
+        assert!(player.press(Key::Next));
+        assert_eq!(player.frame, 0);
+
+        assert!(player.press(Key::Pause));
+        assert!(player.paused);
+        player.press(Key::Previous);
+        assert_eq!(player.frame, 2);
+        player.press(Key::Next);
+        player.press(Key::Next);
+        assert_eq!(player.frame, 1);
+        // stepping past the end isn't a play
+        assert_eq!(player.plays, 0);
+
+        player.press(Key::Pause);
+        assert!(!player.paused);
+        assert!(!player.press(Key::Quit));
+    }
+
+    #[test]
+    fn interrupts_show_the_cursor() {
+        let frames = vec![(b"frame\n".to_vec(), Duration::from_secs(60))];
+        let (sender, events) = mpsc::channel();
+        sender.send(Event::Interrupt).unwrap();
+
+        let mut out = Vec::new();
+        let cursor = HiddenCursor::hide(&mut out).unwrap();
+        assert!(draw_frames(&frames, 0, &events, cursor.out).unwrap());
+        cursor.show().unwrap();
+        assert_eq!(out, b"\x1b[?25lframe\n\x1b[0m\x1b[?25h");
+    }
+
+    #[test]
+    fn finished_animations_stop_taking_interrupts() {
+        let frames = vec![(b"a".to_vec(), Duration::from_millis(0)), (b"b".to_vec(), Duration::from_millis(0))];
+        let mut out = Vec::new();
+        assert!(!play(&frames, 1, false, &mut out).unwrap());
+        assert_eq!(out, b"\x1b[?25la\x1b[Hb\x1b[0m\x1b[?25h");
+        assert!(INTERRUPT.lock().unwrap().is_none());
+    }
+}
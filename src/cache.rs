@@ -0,0 +1,221 @@
+use std::fmt::Debug;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use image::GenericImageView;
+
+use crate::{DynamicImage, PreparedImage, RenderOptions};
+
+// written at the start of every cached image so files from another version are never read as images
+static MAGIC: &[u8] = b"IRC2";
+
+// the most bytes kept in the cache. the images used longest ago are deleted to make room for new ones
+static MAX_SIZE: u64 = 64 * 1024 * 1024;
+
+// 64 bit FNV-1a, which unlike DefaultHasher gives the same hash in every build
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3))
+}
+
+// everything about the file and options that changes the prepared image. colors, dithering, and layout are applied
+// after the cached image so they aren't part of it
+fn key(path: &Path, modified: (u64, u32), len: u64, options: &RenderOptions, decoding: impl Debug) -> String {
+    let o = options;
+    let mut key = format!("{:?} {:?} {} {:?}\n", path, modified, len, decoding);
+    key.push_str(&format!("{:?} {:?} {:?} {:?} {:?} {} {:?} {} {}\n", o.mode, o.size, o.max_width, o.max_height,
+                          o.resize_mode, o.tile, o.filter, o.linear_resize, o.font_ratio));
+    key.push_str(&format!("{:?} {:?} {} {:?} {:?} {:?} {:?}\n", o.tonemap, o.simulate, o.trim, o.trim_color, o.crop,
+                          o.mask, o.radius));
+    key.push_str(&format!("{} {} {} {} {} {} {} {} {:?} {} {} {} {:?} {:?} {:?} {:?}\n", o.flip_h, o.flip_v, o.rotate,
+                          o.grayscale, o.brightness, o.contrast, o.hue, o.invert, o.blur, o.sepia, o.gamma, o.edges,
+                          o.binarize, o.sharpen, o.quantize, o.color_distance));
+    key.push_str(&format!("{:?} {:?} {:?}", o.watermark, o.watermark_corner, o.watermark_color));
+    key
+}
+
+/// Resized images kept on disk so drawing the same file the same way again skips decoding and resizing it
+pub struct Cache {
+    dir: PathBuf
+}
+
+impl Cache {
+    /// The cache in $XDG_CACHE_HOME/image_render or ~/.cache/image_render. None when neither is set
+    pub fn open() -> Option<Self> {
+        let base = match std::env::var_os("XDG_CACHE_HOME").filter(|dir| !dir.is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(std::env::var_os("HOME")?).join(".cache")
+        };
+        Some(Cache { dir: base.join("image_render") })
+    }
+
+    /// Deletes every cached image
+    pub fn clear(&self) -> Result<(), String> {
+        match fs::remove_dir_all(&self.dir) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(format!("{}: {}", self.dir.display(), e)),
+            _ => Ok(())
+        }
+    }
+
+    // the file an image is cached in. files are named after the hash of everything that changes the resized image
+    // so an edited file or different options never find an old one. None for stdin, URLs, and options that have
+    // other images in them
+    fn path(&self, input: &str, options: &RenderOptions, decoding: impl Debug) -> Option<PathBuf> {
+        if input == "-" || input.contains("://") {
+            return None;
+        }
+        if options.overlay.is_some() || options.bg_image.is_some() || options.diff.is_some() {
+            return None;
+        }
+
+        let path = fs::canonicalize(input).ok()?;
+        let metadata = fs::metadata(&path).ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+
+        let key = key(&path, (modified.as_secs(), modified.subsec_nanos()), metadata.len(), options, decoding);
+        Some(self.dir.join(format!("{:016x}", fnv1a(key.as_bytes()))))
+    }
+
+    // deletes the images used longest ago until the cache fits in MAX_SIZE
+    fn evict(&self) -> std::io::Result<()> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            files.push((metadata.modified()?, metadata.len(), entry.path()));
+        }
+        files.sort();
+
+        let mut size: u64 = files.iter().map(|&(_, len, _)| len).sum();
+        for (_, len, path) in files {
+            if size <= MAX_SIZE {
+                break;
+            }
+            fs::remove_file(path)?;
+            size -= len;
+        }
+        Ok(())
+    }
+
+    /// The image drawn from the file at input with the same options before. decoding is anything else that changes
+    /// how the file is decoded
+    pub fn get(&self, input: &str, options: &RenderOptions, decoding: impl Debug) -> Option<PreparedImage> {
+        let path = self.path(input, options, decoding)?;
+        let data = fs::read(&path).ok()?;
+        let data = data.strip_prefix(MAGIC)?;
+
+        let number = |i: usize| {
+            data.get(i * 4..i * 4 + 4).map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        };
+        let (width, height) = (number(0)?, number(1)?);
+        let target = (number(2)?, number(3)?);
+        // cells of 0 by 0 means the image wasn't fit inside a box
+        let cells = Some((number(4)?, number(5)?)).filter(|&cells| cells != (0, 0));
+        let img = image::RgbaImage::from_raw(width, height, data.get(24..)?.to_vec())?;
+
+        // eviction goes by when images were last used, not when they were saved
+        let _ = fs::File::options().write(true).open(&path).and_then(|file| file.set_modified(SystemTime::now()));
+        Some(PreparedImage { img: DynamicImage::ImageRgba8(img), target, cells })
+    }
+
+    /// Saves an image prepared from the file at input so `get` finds it next time. Images that weren't resized,
+    /// like the ones iTerm scales itself, are as big as the file and aren't saved
+    pub fn put(&self, input: &str, options: &RenderOptions, decoding: impl Debug, prepared: &PreparedImage)
+               -> Result<(), String> {
+        let path = match self.path(input, options, decoding) {
+            Some(path) if prepared.img.dimensions() == prepared.target => path,
+            _ => return Ok(())
+        };
+
+        let img = prepared.img.to_rgba8();
+        let cells = prepared.cells.unwrap_or((0, 0));
+        let mut data = MAGIC.to_vec();
+        for number in &[img.width(), img.height(), prepared.target.0, prepared.target.1, cells.0, cells.1] {
+            data.extend_from_slice(&number.to_le_bytes());
+        }
+        data.extend_from_slice(img.as_raw());
+
+        fs::create_dir_all(&self.dir)
+            .and_then(|_| fs::write(&path, data))
+            .and_then(|_| self.evict())
+            .map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{prepare_image, Mode, Size};
+
+    // a cache and an image to draw in a directory of their own
+    fn setup(name: &str) -> (Cache, String) {
+        let dir = std::env::temp_dir().join(format!("image_render_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let input = dir.join("red.png");
+        image::RgbImage::from_pixel(40, 20, image::Rgb([255, 0, 0])).save(&input).unwrap();
+        (Cache { dir: dir.join("cache") }, input.to_string_lossy().into_owned())
+    }
+
+    fn put(cache: &Cache, input: &str, options: &RenderOptions) {
+        let img = image::open(input).unwrap();
+        cache.put(input, options, (), &prepare_image(&img, options).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn same_file_hits() {
+        let (cache, input) = setup("hits");
+        let options = RenderOptions { size: Size::Cells(10, 5), ..RenderOptions::default() };
+        assert!(cache.get(&input, &options, ()).is_none());
+
+        put(&cache, &input, &options);
+        let cached = cache.get(&input, &options, ()).unwrap();
+        assert_eq!(cached.img.dimensions(), cached.target);
+        let _ = fs::remove_dir_all(cache.dir.parent().unwrap());
+    }
+
+    #[test]
+    fn changes_miss() {
+        let (cache, input) = setup("misses");
+        let options = RenderOptions { size: Size::Cells(10, 5), ..RenderOptions::default() };
+        put(&cache, &input, &options);
+
+        let resized = RenderOptions { size: Size::Cells(20, 5), ..options.clone() };
+        assert!(cache.get(&input, &resized, ()).is_none());
+        assert!(cache.get(&input, &options, true).is_none());
+
+        let touched = SystemTime::now() + std::time::Duration::from_secs(60);
+        fs::File::options().write(true).open(&input).unwrap().set_modified(touched).unwrap();
+        assert!(cache.get(&input, &options, ()).is_none());
+        let _ = fs::remove_dir_all(cache.dir.parent().unwrap());
+    }
+
+    #[test]
+    fn unresized_images_are_skipped() {
+        let (cache, input) = setup("unresized");
+        let options = RenderOptions { mode: Mode::Iterm, size: Size::Cells(10, 5), ..RenderOptions::default() };
+        put(&cache, &input, &options);
+        assert!(cache.get(&input, &options, ()).is_none());
+        let _ = fs::remove_dir_all(cache.dir.parent().unwrap());
+    }
+
+    #[test]
+    fn old_images_are_evicted() {
+        let (cache, _) = setup("evicted");
+        fs::create_dir_all(&cache.dir).unwrap();
+        let big = vec![0; (MAX_SIZE / 2) as usize];
+        for (i, name) in ["a", "b", "c"].iter().enumerate() {
+            let path = cache.dir.join(name);
+            fs::write(&path, &big).unwrap();
+            let modified = UNIX_EPOCH + std::time::Duration::from_secs(i as u64 * 60);
+            fs::File::options().write(true).open(&path).unwrap().set_modified(modified).unwrap();
+        }
+
+        cache.evict().unwrap();
+        assert!(!cache.dir.join("a").exists());
+        assert!(cache.dir.join("b").exists() && cache.dir.join("c").exists());
+        let _ = fs::remove_dir_all(cache.dir.parent().unwrap());
+    }
+}
@@ -0,0 +1,346 @@
+use std::io::Write;
+
+use crate::color::{color_distance, color_escape, luminance, palette_color, ColorDistance, Colors};
+use crate::progress::Progress;
+use crate::{DynamicImage, DEFAULT_CHARSET};
+
+// renders each line of cells on its own thread with the rayon feature. lines are written in order either way so
+// the output is the same. progress counts each line as it's rendered, whichever thread renders it
+fn write_lines(count: u32, line: impl Fn(u32) -> String + Sync, progress: Option<&Progress>, out: &mut impl Write)
+               -> std::io::Result<()> {
+    let line = |y| {
+        let line = line(y);
+        if let Some(progress) = progress {
+            progress.line();
+        }
+        line
+    };
+    #[cfg(feature = "rayon")]
+    let lines: Vec<String> = {
+        use rayon::prelude::*;
+        (0..count).into_par_iter().map(line).collect()
+    };
+    #[cfg(not(feature = "rayon"))]
+    let lines = (0..count).map(line);
+
+    for line in lines {
+        out.write_all(line.as_bytes())?;
+    }
+    Ok(())
+}
+
+pub(crate) fn write_blocks(img: &DynamicImage, colors: Colors, distance: ColorDistance, cell: &str,
+                           progress: Option<&Progress>, out: &mut impl Write) -> std::io::Result<()> {
+    let img = img.to_rgb8();
+
+    write_lines(img.height(), |y| {
+        let mut line = String::new();
+        // neighboring cells with the same color only need one escape
+        let mut last_escape = None;
+
+        for x in 0..img.width() {
+            let pixel = img.get_pixel(x, y);
+            let mut escape = color_escape(pixel.0, colors, distance, true);
+
+            // anything other than a space is drawn with the foreground color
+            if cell != " " {
+                escape.push_str(&color_escape(pixel.0, colors, distance, false));
+            }
+
+            if last_escape.as_ref() != Some(&escape) {
+                line.push_str(&escape);
+                last_escape = Some(escape);
+            }
+
+            line.push_str(cell);
+        }
+
+        line.push_str("\x1b[0m\n");
+        line
+    }, progress, out)
+}
+
+// reset clears the colors after every cell. without it each cell's escapes override the last cell's, so they're only
+// written when they change and the colors are cleared at the end of the line
+pub(crate) fn write_halfblocks(img: &DynamicImage, colors: Colors, distance: ColorDistance, reset: bool,
+                               progress: Option<&Progress>, out: &mut impl Write) -> std::io::Result<()> {
+    let img = img.to_rgb8();
+
+    write_lines(img.height().div_ceil(2), |line_index| {
+        let y = line_index * 2;
+        let mut line = String::new();
+        let mut last_escape = None;
+
+        for x in 0..img.width() {
+            let top = img.get_pixel(x, y).0;
+
+            let mut escape = color_escape(top, colors, distance, false);
+
+            // odd heights leave the last bottom half on the terminal's default background
+            if y + 1 < img.height() {
+                let bottom = img.get_pixel(x, y + 1).0;
+                escape.push_str(&color_escape(bottom, colors, distance, true));
+            }
+
+            if reset {
+                line.push_str(&escape);
+                line.push_str("\u{2580}\x1b[0m");
+                continue;
+            }
+
+            if last_escape.as_ref() != Some(&escape) {
+                line.push_str(&escape);
+                last_escape = Some(escape);
+            }
+            line.push('\u{2580}');
+        }
+
+        if !reset {
+            line.push_str("\x1b[0m");
+        }
+        line.push('\n');
+        line
+    }, progress, out)
+}
+
+// quadrant characters indexed by the quarters drawn in the foreground color. bit 0 is the top left, bit 1 the top
+// right, bit 2 the bottom left, and bit 3 the bottom right
+static QUADRANTS: [char; 16] = [
+    ' ', '\u{2598}', '\u{259d}', '\u{2580}', '\u{2596}', '\u{258c}', '\u{259e}', '\u{259b}',
+    '\u{2597}', '\u{259a}', '\u{2590}', '\u{259c}', '\u{2584}', '\u{2599}', '\u{259f}', '\u{2588}'
+];
+
+fn average(colors: &[[u8; 3]]) -> [u8; 3] {
+    let mut sum = [0; 3];
+    for color in colors {
+        for i in 0..3 {
+            sum[i] += u32::from(color[i]);
+        }
+    }
+    let count = colors.len() as u32;
+    let channel = |i: usize| ((sum[i] + count / 2) / count) as u8;
+    [channel(0), channel(1), channel(2)]
+}
+
+// splits the pixels of a cell into the two groups closest to their own average color and returns the quarters in
+// the first group with the colors of both. a cell of one color is all background
+fn split_quadrant(pixels: &[[u8; 3]], distance: ColorDistance) -> (usize, [u8; 3], [u8; 3]) {
+    let uniform = average(pixels);
+    let error = |group: &[[u8; 3]], color: [u8; 3]| {
+        group.iter().map(|&pixel| color_distance(pixel, color, distance)).sum::<u32>()
+    };
+    let mut best = (0, uniform, uniform);
+    let mut best_error = error(pixels, uniform);
+
+    // masks past the last pixel would leave one group empty. the top bit is left out since flipping every bit
+    // gives the same split with the colors swapped
+    for mask in 1..(1 << (pixels.len() - 1)) {
+        let (fg, bg): (Vec<_>, Vec<_>) = (0..pixels.len()).partition(|&i| mask & (1 << i) != 0);
+        let fg: Vec<[u8; 3]> = fg.into_iter().map(|i| pixels[i]).collect();
+        let bg: Vec<[u8; 3]> = bg.into_iter().map(|i| pixels[i]).collect();
+        let (fg_color, bg_color) = (average(&fg), average(&bg));
+
+        let split_error = error(&fg, fg_color) + error(&bg, bg_color);
+        if split_error < best_error {
+            best = (mask, fg_color, bg_color);
+            best_error = split_error;
+        }
+    }
+    best
+}
+
+pub(crate) fn write_quadrants(img: &DynamicImage, colors: Colors, distance: ColorDistance, progress: Option<&Progress>,
+                              out: &mut impl Write)
+                              -> std::io::Result<()> {
+    let img = img.to_rgb8();
+    let (width, height) = img.dimensions();
+
+    write_lines(height.div_ceil(2), |line_index| {
+        let y = line_index * 2;
+        let mut line = String::new();
+        let mut last_escape = None;
+
+        for x in (0..width).step_by(2) {
+            // quarters past the right or bottom edge of odd sized images come out as background
+            let mut pixels = Vec::new();
+            let mut quarters = Vec::new();
+            for (quarter, (dx, dy)) in [(0, 0), (1, 0), (0, 1), (1, 1)].iter().enumerate() {
+                if x + dx < width && y + dy < height {
+                    pixels.push(img.get_pixel(x + dx, y + dy).0);
+                    quarters.push(quarter);
+                }
+            }
+
+            let (mask, fg, bg) = split_quadrant(&pixels, distance);
+            let mut glyph = quarters.iter().enumerate()
+                .filter(|&(i, _)| mask & (1 << i) != 0)
+                .fold(0, |glyph, (_, &quarter)| glyph | (1 << quarter));
+
+            // both halves can end up as the same color of the terminal's palette
+            if palette_color(fg, colors, distance) == palette_color(bg, colors, distance) {
+                glyph = 0;
+            }
+
+            let mut escape = color_escape(bg, colors, distance, true);
+            if glyph != 0 {
+                escape.push_str(&color_escape(fg, colors, distance, false));
+            }
+
+            if last_escape.as_ref() != Some(&escape) {
+                line.push_str(&escape);
+                last_escape = Some(escape);
+            }
+
+            line.push(QUADRANTS[glyph]);
+        }
+
+        line.push_str("\x1b[0m\n");
+        line
+    }, progress, out)
+}
+
+// shades mode draws these from darkest to lightest like a charset
+pub(crate) static SHADES: [char; 5] = [' ', '\u{2591}', '\u{2592}', '\u{2593}', '\u{2588}'];
+
+// the pixels are drawn with charset from darkest to lightest, or with the default charset when it's empty
+pub(crate) fn write_ascii(img: &DynamicImage, charset: &[char], progress: Option<&Progress>, out: &mut impl Write)
+                          -> std::io::Result<()> {
+    let img = img.to_rgb8();
+    let default: Vec<char>;
+    let charset = if charset.is_empty() {
+        default = DEFAULT_CHARSET.chars().collect();
+        &default
+    } else {
+        charset
+    };
+    let last = (charset.len() - 1) as f32;
+
+    write_lines(img.height(), |y| {
+        let mut line: String = (0..img.width())
+            .map(|x| charset[(luminance(img.get_pixel(x, y).0) / 255.0 * last).round() as usize])
+            .collect();
+        line.push('\n');
+        line
+    }, progress, out)
+}
+
+// bit of each dot in a braille character indexed by [y][x]
+static BRAILLE_DOTS: [[u32; 2]; 4] = [
+    [0x01, 0x08],
+    [0x02, 0x10],
+    [0x04, 0x20],
+    [0x40, 0x80]
+];
+
+pub(crate) fn write_braille(img: &DynamicImage, threshold: u8, progress: Option<&Progress>, out: &mut impl Write)
+                            -> std::io::Result<()> {
+    let img = img.to_rgb8();
+
+    write_lines(img.height().div_ceil(4), |line_index| {
+        let y = line_index * 4;
+        let mut line = String::new();
+
+        for x in (0..img.width()).step_by(2) {
+            let mut bits = 0;
+
+            // tiles on the right and bottom edges may be cut off
+            for dy in 0..4.min(img.height() - y) {
+                for dx in 0..2.min(img.width() - x) {
+                    if luminance(img.get_pixel(x + dx, y + dy).0) >= f32::from(threshold) {
+                        bits |= BRAILLE_DOTS[dy as usize][dx as usize];
+                    }
+                }
+            }
+
+            line.push(std::char::from_u32(0x2800 + bits).unwrap());
+        }
+
+        line.push('\n');
+        line
+    }, progress, out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn solid_rows_use_one_escape() {
+        let img = DynamicImage::ImageRgb8(image::RgbImage::from_pixel(8, 2, image::Rgb([255, 0, 0])));
+        let mut out = Vec::new();
+        write_blocks(&img, Colors::TrueColor, ColorDistance::Rgb, " ", None, &mut out).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        for line in out.lines() {
+            assert_eq!(line, "\x1b[48;2;255;0;0m        \x1b[0m");
+            assert_eq!(line.matches('\x1b').count(), 2);
+        }
+        assert_eq!(out.lines().count(), 2);
+    }
+
+    // only renders in parallel with --features rayon
+    #[test]
+    fn parallel_lines_match_serial() {
+        let line = |y: u32| format!("{}{}\n", y, "x".repeat(y as usize % 7));
+        let mut out = Vec::new();
+        write_lines(200, line, None, &mut out).unwrap();
+
+        let serial: String = (0..200).map(line).collect();
+        assert_eq!(out, serial.into_bytes());
+    }
+
+    #[test]
+    fn quadrant_splits() {
+        let (red, green) = ([255, 0, 0], [0, 255, 0]);
+
+        // the top right and bottom left quarters are drawn in the foreground color
+        assert_eq!(split_quadrant(&[red, green, green, red], ColorDistance::Rgb), (6, green, red));
+        assert_eq!(split_quadrant(&[green, red, red, green], ColorDistance::Rgb), (6, red, green));
+        assert_eq!(QUADRANTS[6], '\u{259e}');
+
+        assert_eq!(split_quadrant(&[red; 4], ColorDistance::Rgb), (0, red, red));
+    }
+
+    #[test]
+    fn empty_charsets_use_the_default() {
+        let img = DynamicImage::ImageLuma8(image::GrayImage::from_fn(2, 1, |x, _| image::Luma([x as u8 * 255])));
+        let mut out = Vec::new();
+        write_ascii(&img, &[], None, &mut out).unwrap();
+        assert_eq!(out, b" @\n");
+    }
+
+    #[test]
+    fn gradients_span_the_charset() {
+        let values = [0, 63, 128, 192, 255];
+        let img = DynamicImage::ImageLuma8(image::GrayImage::from_fn(5, 1, |x, _| image::Luma([values[x as usize]])));
+        let mut out = Vec::new();
+        write_ascii(&img, &[' ', '.', '#'], None, &mut out).unwrap();
+        assert_eq!(out, b"  .##\n");
+    }
+
+    #[test]
+    fn no_reset_writes_colors_once() {
+        let img = DynamicImage::ImageRgb8(image::RgbImage::from_pixel(8, 2, image::Rgb([255, 0, 0])));
+        let halfblocks = |reset| {
+            let mut out = Vec::new();
+            write_halfblocks(&img, Colors::TrueColor, ColorDistance::Rgb, reset, None, &mut out).unwrap();
+            out
+        };
+
+        // each cell is a foreground and background escape, a three byte half block, and a reset
+        let escapes = "\x1b[38;2;255;0;0m\x1b[48;2;255;0;0m".len();
+        assert_eq!(halfblocks(true).len(), 8 * (escapes + 3 + 4) + 1);
+        // without resets the colors are written once and cleared at the end of the line
+        let no_reset = halfblocks(false);
+        assert_eq!(no_reset.len(), escapes + 8 * 3 + 4 + 1);
+        assert_eq!(no_reset, format!("\x1b[38;2;255;0;0m\x1b[48;2;255;0;0m{}\x1b[0m\n", "\u{2580}".repeat(8)).into_bytes());
+    }
+
+    #[test]
+    fn shades_of_black_and_white() {
+        let img = DynamicImage::ImageLuma8(image::GrayImage::from_fn(2, 1, |x, _| image::Luma([if x == 0 { 0 } else { 255 }])));
+        let mut out = Vec::new();
+        write_ascii(&img, &SHADES, None, &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), " \u{2588}\n");
+    }
+}
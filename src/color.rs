@@ -0,0 +1,305 @@
+use crate::DynamicImage;
+
+/// Names of the colors accepted by `get_colors`
+pub static COLORS: [&str; 3] = [
+    "truecolor",
+    "256",
+    "16"
+];
+
+/// Colors supported by the terminal
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Colors {
+    TrueColor,
+    Ansi256,
+    Ansi16
+}
+
+pub fn get_colors(colors: &str) -> Option<Colors> {
+    match colors {
+        "truecolor" => Some(Colors::TrueColor),
+        "256" => Some(Colors::Ansi256),
+        "16" => Some(Colors::Ansi16),
+        _ => None
+    }
+}
+
+/// Guesses the colors supported by the terminal from its environment
+pub fn detect_colors() -> Colors {
+    let var = |name: &str| std::env::var(name).unwrap_or_default();
+    colors_from_env(&var("COLORTERM"), &var("TERM"))
+}
+
+// unset variables are empty
+fn colors_from_env(colorterm: &str, term: &str) -> Colors {
+    if colorterm == "truecolor" || colorterm == "24bit" {
+        return Colors::TrueColor;
+    }
+
+    if term.contains("256color") {
+        Colors::Ansi256
+    } else {
+        Colors::Ansi16
+    }
+}
+
+/// Colors are written as RRGGBB with an optional #
+pub fn parse_color(color: &str) -> Option<[u8; 3]> {
+    let hex = color.trim_start_matches('#');
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+    Some([channel(0), channel(2), channel(4)])
+}
+
+pub(crate) fn luminance(color: [u8; 3]) -> f32 {
+    0.299 * f32::from(color[0]) + 0.587 * f32::from(color[1]) + 0.114 * f32::from(color[2])
+}
+
+// an sRGB value from 0 to 1 to linear light from 0 to 1
+pub(crate) fn to_linear(value: f32) -> f32 {
+    if value <= 0.040_45 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+// linear light from 0 to 1 to an sRGB value from 0 to 1
+pub(crate) fn from_linear(linear: f32) -> f32 {
+    let linear = linear.clamp(0.0, 1.0);
+    if linear <= 0.003_130_8 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Names of the color distances accepted by `get_color_distance`
+pub static COLOR_DISTANCES: [&str; 2] = [
+    "rgb",
+    "redmean"
+];
+
+/// How close two colors are judged to be when picking from the terminal's palette
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ColorDistance {
+    /// Straight line distance between the RGB values
+    Rgb,
+    /// RGB distance weighted by how red the colors are, which is closer to how different they look
+    Redmean
+}
+
+pub fn get_color_distance(distance: &str) -> Option<ColorDistance> {
+    match distance {
+        "rgb" => Some(ColorDistance::Rgb),
+        "redmean" => Some(ColorDistance::Redmean),
+        _ => None
+    }
+}
+
+pub(crate) fn color_distance(a: [u8; 3], b: [u8; 3], distance: ColorDistance) -> u32 {
+    let delta = |i: usize| (i32::from(a[i]) - i32::from(b[i])).pow(2) as u32;
+    match distance {
+        ColorDistance::Rgb => delta(0) + delta(1) + delta(2),
+        // (2 + r/256) * dr^2 + 4 * dg^2 + (2 + (255 - r)/256) * db^2 where r is the average red
+        ColorDistance::Redmean => {
+            let red = (u32::from(a[0]) + u32::from(b[0])) / 2;
+            (((512 + red) * delta(0)) >> 8) + 4 * delta(1) + (((767 - red) * delta(2)) >> 8)
+        }
+    }
+}
+
+// levels used by each channel of the 6x6x6 color cube
+static CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+// searches the whole palette for the closest color. the cube and grayscale ramp overlap in ways that make
+// picking the nearest level of each one miss better matches. ties go to the cube and ramp since themes often
+// change the 16 standard colors
+pub(crate) fn to_ansi256(color: [u8; 3], distance: ColorDistance) -> u8 {
+    let closest = |candidates: &mut dyn Iterator<Item = u8>| {
+        candidates.min_by_key(|&i| color_distance(color, ansi256_color(i), distance)).unwrap()
+    };
+    // grays, like from --grayscale, only use the grays of the ramp and cube. the standard grays are spaced unevenly
+    // and make gradients band
+    if color[0] == color[1] && color[1] == color[2] {
+        return closest(&mut CUBE_GRAYS.iter().copied().chain(232..=255));
+    }
+    closest(&mut (16..=255).chain(0..16))
+}
+
+// entries of the cube with the same level for every channel, from black to white
+static CUBE_GRAYS: [u8; 6] = [16, 59, 102, 145, 188, 231];
+
+// default RGB values of the 16 standard ANSI colors
+static ANSI16: [[u8; 3]; 16] = [
+    [0, 0, 0],
+    [128, 0, 0],
+    [0, 128, 0],
+    [128, 128, 0],
+    [0, 0, 128],
+    [128, 0, 128],
+    [0, 128, 128],
+    [192, 192, 192],
+    [128, 128, 128],
+    [255, 0, 0],
+    [0, 255, 0],
+    [255, 255, 0],
+    [0, 0, 255],
+    [255, 0, 255],
+    [0, 255, 255],
+    [255, 255, 255]
+];
+
+pub(crate) fn to_ansi16(color: [u8; 3], distance: ColorDistance) -> u8 {
+    (0..ANSI16.len()).min_by_key(|&i| color_distance(color, ANSI16[i], distance)).unwrap() as u8
+}
+
+// RGB value of an entry of the 256 color palette
+fn ansi256_color(index: u8) -> [u8; 3] {
+    match index {
+        0..=15 => ANSI16[usize::from(index)],
+        16..=231 => {
+            let cube = usize::from(index - 16);
+            [CUBE_LEVELS[cube / 36], CUBE_LEVELS[cube / 6 % 6], CUBE_LEVELS[cube % 6]]
+        }
+        _ => [8 + (index - 232) * 10; 3]
+    }
+}
+
+// closest color the terminal can draw
+pub(crate) fn palette_color(color: [u8; 3], colors: Colors, distance: ColorDistance) -> [u8; 3] {
+    match colors {
+        Colors::TrueColor => color,
+        Colors::Ansi256 => ansi256_color(to_ansi256(color, distance)),
+        Colors::Ansi16 => ANSI16[usize::from(to_ansi16(color, distance))]
+    }
+}
+
+// ESC[38;...m sets the foreground color and ESC[48;...m sets the background color
+pub(crate) fn color_escape(color: [u8; 3], colors: Colors, distance: ColorDistance, background: bool) -> String {
+    let layer = if background { 48 } else { 38 };
+    match colors {
+        // ANSI true color (8 bit RGB): ESC[48;2;R;G;Bm
+        Colors::TrueColor => format!("\x1b[{};2;{};{};{}m", layer, color[0], color[1], color[2]),
+        // xterm 256 color palette: ESC[48;5;Nm
+        Colors::Ansi256 => format!("\x1b[{};5;{}m", layer, to_ansi256(color, distance)),
+        // standard colors are ESC[40m-ESC[47m and bright colors are ESC[100m-ESC[107m
+        Colors::Ansi16 => match to_ansi16(color, distance) {
+            index @ 0..=7 => format!("\x1b[{}m", layer - 8 + index),
+            index => format!("\x1b[{}m", layer + 52 + index - 8)
+        }
+    }
+}
+
+// squares of the checkerboard drawn behind transparent pixels, starting with light in the top left
+static CHECKER: [[u8; 3]; 2] = [[204, 204, 204], [153, 153, 153]];
+
+// blends each pixel with the background based on its alpha. checker is the size of the squares of a checkerboard
+// drawn instead of background
+pub(crate) fn composite(img: &DynamicImage, background: [u8; 3], checker: Option<u32>) -> DynamicImage {
+    let img = img.to_rgba8();
+
+    DynamicImage::ImageRgb8(image::RgbImage::from_fn(img.width(), img.height(), |x, y| {
+        let pixel = img.get_pixel(x, y).0;
+        let alpha = u32::from(pixel[3]);
+        let background = match checker {
+            Some(size) => CHECKER[((x / size + y / size) % 2) as usize],
+            None => background
+        };
+
+        let mut color = [0; 3];
+        for i in 0..3 {
+            color[i] = ((u32::from(pixel[i]) * alpha + u32::from(background[i]) * (255 - alpha) + 127) / 255) as u8;
+        }
+        image::Rgb(color)
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grays_map_monotonically() {
+        let indexes: Vec<u8> = (0..=255).map(|gray| to_ansi256([gray; 3], ColorDistance::Rgb)).collect();
+        let levels: Vec<u8> = indexes.iter().map(|&i| ansi256_color(i)[0]).collect();
+        assert!(levels.windows(2).all(|pair| pair[0] <= pair[1]));
+        // every step of the ramp is used
+        assert!((232..=255).all(|i| indexes.contains(&i)));
+        // exact grays from the cube aren't moved to the ramp
+        assert_eq!(to_ansi256([95; 3], ColorDistance::Rgb), 59);
+        assert_eq!(to_ansi256([135; 3], ColorDistance::Rgb), 102);
+    }
+
+    #[test]
+    fn ansi256_indexes() {
+        assert_eq!(to_ansi256([255, 0, 0], ColorDistance::Rgb), 196);
+        assert_eq!(to_ansi256([0, 0, 255], ColorDistance::Rgb), 21);
+        assert_eq!(to_ansi256([95, 135, 175], ColorDistance::Rgb), 67);
+        assert_eq!(to_ansi256([128, 128, 128], ColorDistance::Rgb), 244);
+        assert_eq!(to_ansi256([0, 0, 0], ColorDistance::Rgb), 16);
+        assert_eq!(to_ansi256([255, 255, 255], ColorDistance::Rgb), 231);
+    }
+
+    #[test]
+    fn ansi16_indexes() {
+        assert_eq!(to_ansi16([128, 0, 0], ColorDistance::Rgb), 1);
+        assert_eq!(to_ansi16([0, 0, 255], ColorDistance::Rgb), 12);
+        assert_eq!(to_ansi16([255, 255, 255], ColorDistance::Rgb), 15);
+        assert_eq!(to_ansi16([0, 0, 0], ColorDistance::Rgb), 0);
+        assert_eq!(color_escape([0, 0, 255], Colors::Ansi16, ColorDistance::Rgb, true), "\x1b[104m");
+    }
+
+    #[test]
+    fn composite_over_background() {
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::from_fn(3, 1, |x, _| match x {
+            0 => image::Rgba([200, 100, 50, 0]),
+            1 => image::Rgba([200, 100, 50, 255]),
+            _ => image::Rgba([200, 100, 50, 128])
+        }));
+        let pixels = |background| composite(&img, background, None).to_rgb8().pixels().map(|p| p.0).collect::<Vec<_>>();
+
+        assert_eq!(pixels([0, 0, 0]), [[0, 0, 0], [200, 100, 50], [100, 50, 25]]);
+        assert_eq!(pixels([255, 255, 255]), [[255, 255, 255], [200, 100, 50], [227, 177, 152]]);
+    }
+
+    #[test]
+    fn checkerboards() {
+        let transparent = DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(4, 4, image::Rgba([255, 0, 0, 0])));
+        let checked = composite(&transparent, [0, 0, 0], Some(2)).to_rgb8();
+        for (x, y, pixel) in checked.enumerate_pixels() {
+            assert_eq!(pixel.0, CHECKER[((x / 2 + y / 2) % 2) as usize], "{},{}", x, y);
+        }
+        assert_ne!(checked.get_pixel(0, 0), checked.get_pixel(2, 0));
+
+        let opaque = DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(4, 4, image::Rgba([255, 0, 0, 255])));
+        assert!(composite(&opaque, [0, 0, 0], Some(2)).to_rgb8().pixels().all(|pixel| pixel.0 == [255, 0, 0]));
+    }
+
+    #[test]
+    fn colors_from_environment() {
+        assert_eq!(colors_from_env("truecolor", "xterm"), Colors::TrueColor);
+        assert_eq!(colors_from_env("24bit", ""), Colors::TrueColor);
+        assert_eq!(colors_from_env("", "xterm-256color"), Colors::Ansi256);
+        assert_eq!(colors_from_env("truecolor", "xterm-256color"), Colors::TrueColor);
+        assert_eq!(colors_from_env("", "dumb"), Colors::Ansi16);
+        assert_eq!(colors_from_env("", ""), Colors::Ansi16);
+    }
+
+    #[test]
+    fn distances_pick_documented_indexes() {
+        use ColorDistance::*;
+        assert_eq!(to_ansi256([48, 249, 14], Rgb), 82);
+        assert_eq!(to_ansi256([48, 249, 14], Redmean), 46);
+        assert_eq!(to_ansi256([48, 113, 204], Rgb), 62);
+        assert_eq!(to_ansi256([48, 113, 204], Redmean), 26);
+        assert_eq!(to_ansi256([255, 0, 0], Rgb), 196);
+        assert_eq!(to_ansi256([255, 0, 0], Redmean), 196);
+        assert_eq!(to_ansi256([0, 0, 255], Redmean), 21);
+        assert_eq!(to_ansi256([255, 128, 0], Rgb), 208);
+        assert_eq!(to_ansi256([255, 128, 0], Redmean), 208);
+    }
+}
@@ -0,0 +1,227 @@
+use crate::color::{palette_color, ColorDistance, Colors};
+use crate::DynamicImage;
+
+/// Names of the dithering methods accepted by `get_dither`
+pub static DITHERS: [&str; 4] = [
+    "none",
+    "floyd",
+    "ordered",
+    "random"
+];
+
+/// How gradients are smoothed when colors are reduced to the palette of the terminal
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Dither {
+    None,
+    /// Floyd-Steinberg error diffusion. With a seed, noise picked from it is added to each pixel to break up the
+    /// patterns diffusion leaves in flat areas
+    Floyd,
+    /// Bayer matrix thresholds. The same image always dithers the same way
+    Ordered,
+    /// Noise picked from a seed. The same seed always dithers an image the same way
+    Random
+}
+
+pub fn get_dither(dither: &str) -> Option<Dither> {
+    match dither {
+        "none" => Some(Dither::None),
+        "floyd" => Some(Dither::Floyd),
+        "ordered" => Some(Dither::Ordered),
+        "random" => Some(Dither::Random),
+        _ => None
+    }
+}
+
+/// Sizes of the Bayer matrices used by ordered dithering
+pub static DITHER_MATRICES: [&str; 3] = [
+    "2",
+    "4",
+    "8"
+];
+
+// random dithering without a seed uses 0
+pub(crate) fn dither(img: &DynamicImage, colors: Colors, distance: ColorDistance, dither: Dither, matrix: u32,
+                     seed: Option<u64>) -> DynamicImage {
+    match dither {
+        Dither::None => img.clone(),
+        Dither::Floyd => floyd_steinberg(img, colors, distance, seed),
+        Dither::Ordered => ordered(img, colors, distance, matrix),
+        Dither::Random => random(img, colors, distance, seed.unwrap_or(0))
+    }
+}
+
+// replaces every pixel with a palette color and pushes the difference onto the pixels that haven't been
+// drawn yet so the average color of an area stays the same. a seed nudges each pixel by noise before its palette
+// color is picked. the error is still from the pixel without the noise so the average doesn't change
+fn floyd_steinberg(img: &DynamicImage, colors: Colors, distance: ColorDistance, seed: Option<u64>) -> DynamicImage {
+    let mut img = img.to_rgb8();
+    let (width, height) = (img.width() as usize, img.height() as usize);
+    let spread = palette_spread(colors);
+
+    let mut pixels: Vec<[f32; 3]> = img.pixels().map(|p| [f32::from(p[0]), f32::from(p[1]), f32::from(p[2])]).collect();
+
+    for y in 0..height {
+        for x in 0..width {
+            let old = pixels[y * width + x];
+            let jitter = seed.map_or(0.0, |seed| noise(seed, x as u32, y as u32) * spread);
+            let mut nudged = [0; 3];
+            for i in 0..3 {
+                nudged[i] = (old[i] + jitter).round().clamp(0.0, 255.0) as u8;
+            }
+            let new = palette_color(nudged, colors, distance);
+            img.put_pixel(x as u32, y as u32, image::Rgb(new));
+
+            let error = [old[0] - f32::from(new[0]), old[1] - f32::from(new[1]), old[2] - f32::from(new[2])];
+            let mut spread = |x: usize, y: usize, weight: f32| {
+                if x < width && y < height {
+                    let pixel = &mut pixels[y * width + x];
+                    for i in 0..3 {
+                        pixel[i] = (pixel[i] + error[i] * weight).clamp(0.0, 255.0);
+                    }
+                }
+            };
+
+            spread(x + 1, y, 7.0 / 16.0);
+            // wraps to usize::MAX on the left edge which is skipped like any other pixel outside the image
+            spread(x.wrapping_sub(1), y + 1, 3.0 / 16.0);
+            spread(x, y + 1, 5.0 / 16.0);
+            spread(x + 1, y + 1, 1.0 / 16.0);
+        }
+    }
+
+    DynamicImage::ImageRgb8(img)
+}
+
+static BAYER_2: [[u32; 2]; 2] = [
+    [0, 2],
+    [3, 1]
+];
+
+// each quadrant of a Bayer matrix is the matrix half its size with BAYER_2 filling in the low bits
+fn bayer(x: u32, y: u32, size: u32) -> u32 {
+    if size == 1 {
+        return 0;
+    }
+    let half = size / 2;
+    4 * bayer(x % half, y % half, half) + BAYER_2[(y / half) as usize][(x / half) as usize]
+}
+
+// about how far apart neighboring palette colors are. pixels are nudged by up to half of this
+fn palette_spread(colors: Colors) -> f32 {
+    match colors {
+        Colors::TrueColor => 0.0,
+        Colors::Ansi256 => 40.0,
+        Colors::Ansi16 => 128.0
+    }
+}
+
+// nudges each pixel by the threshold of its spot in a tiled Bayer matrix before picking a palette color
+fn ordered(img: &DynamicImage, colors: Colors, distance: ColorDistance, matrix: u32) -> DynamicImage {
+    let mut img = img.to_rgb8();
+    let spread = palette_spread(colors);
+
+    for (x, y, pixel) in img.enumerate_pixels_mut() {
+        // from -0.5 to 0.5
+        let threshold = (bayer(x % matrix, y % matrix, matrix) as f32 + 0.5) / (matrix * matrix) as f32 - 0.5;
+        let mut color = [0; 3];
+        for i in 0..3 {
+            color[i] = (f32::from(pixel[i]) + threshold * spread).round().clamp(0.0, 255.0) as u8;
+        }
+        *pixel = image::Rgb(palette_color(color, colors, distance));
+    }
+
+    DynamicImage::ImageRgb8(img)
+}
+
+// splitmix64 of the seed and position. every pixel gets its own noise so it doesn't depend on the order pixels are
+// visited and the frames of an animation don't flicker
+fn noise(seed: u64, x: u32, y: u32) -> f32 {
+    let mut z = seed ^ (u64::from(y) << 32 | u64::from(x)).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^= z >> 31;
+    // the top 24 bits fit in an f32 exactly. from -0.5 to 0.5
+    (z >> 40) as f32 / (1 << 24) as f32 - 0.5
+}
+
+// nudges each pixel by noise before picking a palette color, like ordered dithering without the pattern
+fn random(img: &DynamicImage, colors: Colors, distance: ColorDistance, seed: u64) -> DynamicImage {
+    let mut img = img.to_rgb8();
+    let spread = palette_spread(colors);
+
+    for (x, y, pixel) in img.enumerate_pixels_mut() {
+        let threshold = noise(seed, x, y);
+        let mut color = [0; 3];
+        for i in 0..3 {
+            color[i] = (f32::from(pixel[i]) + threshold * spread).round().clamp(0.0, 255.0) as u8;
+        }
+        *pixel = image::Rgb(palette_color(color, colors, distance));
+    }
+
+    DynamicImage::ImageRgb8(img)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn floyd_keeps_the_average() {
+        // halfway between two grays of the 16 color palette
+        let img = DynamicImage::ImageRgb8(image::RgbImage::from_pixel(32, 32, image::Rgb([160, 160, 160])));
+        let dithered = floyd_steinberg(&img, Colors::Ansi16, ColorDistance::Rgb, None).to_rgb8();
+
+        let mut grays: Vec<u8> = dithered.pixels().map(|p| p[0]).collect();
+        let average = grays.iter().map(|&g| f32::from(g)).sum::<f32>() / grays.len() as f32;
+        assert!((average - 160.0).abs() < 4.0, "average {}", average);
+
+        grays.sort_unstable();
+        grays.dedup();
+        assert_eq!(grays, [128, 192]);
+        assert!(dithered.pixels().all(|p| p[0] == p[1] && p[1] == p[2]));
+    }
+
+    #[test]
+    fn bayer_matrices_use_every_threshold() {
+        for &size in &[2, 4, 8] {
+            let mut thresholds: Vec<u32> = (0..size * size).map(|i| bayer(i % size, i / size, size)).collect();
+            thresholds.sort_unstable();
+            assert_eq!(thresholds, (0..size * size).collect::<Vec<_>>(), "size {}", size);
+        }
+    }
+
+    #[test]
+    fn ordered_is_deterministic() {
+        let img = DynamicImage::ImageRgb8(image::RgbImage::from_fn(32, 8, |x, _| image::Rgb([(x * 8) as u8; 3])));
+        let render = |matrix| ordered(&img, Colors::Ansi16, ColorDistance::Rgb, matrix).to_bytes();
+
+        assert_eq!(render(8), render(8));
+        assert_ne!(render(8), render(2));
+    }
+
+    #[test]
+    fn seeds_repeat() {
+        let img = DynamicImage::ImageRgb8(image::RgbImage::from_fn(16, 16, |x, y| image::Rgb([(x * 16) as u8, (y * 16) as u8, 100])));
+        let render = |seed| random(&img, Colors::Ansi16, ColorDistance::Rgb, seed).to_bytes();
+
+        assert_eq!(render(1), render(1));
+        assert_ne!(render(1), render(2));
+        assert_ne!(noise(1, 3, 4), noise(2, 3, 4));
+    }
+
+    #[test]
+    fn seeds_jitter_floyd() {
+        let img = DynamicImage::ImageRgb8(image::RgbImage::from_fn(16, 16, |x, y| image::Rgb([(x * 16) as u8, (y * 16) as u8, 100])));
+        let render = |seed| floyd_steinberg(&img, Colors::Ansi16, ColorDistance::Rgb, seed).to_bytes();
+
+        assert_eq!(render(Some(1)), render(Some(1)));
+        assert_ne!(render(Some(1)), render(Some(2)));
+        assert_ne!(render(Some(1)), render(None));
+
+        // the noise doesn't change the average
+        let gray = DynamicImage::ImageRgb8(image::RgbImage::from_pixel(32, 32, image::Rgb([160, 160, 160])));
+        let dithered = floyd_steinberg(&gray, Colors::Ansi16, ColorDistance::Rgb, Some(7)).to_rgb8();
+        let average = dithered.pixels().map(|p| f32::from(p[0])).sum::<f32>() / (32 * 32) as f32;
+        assert!((average - 160.0).abs() < 4.0, "average {}", average);
+    }
+}
@@ -0,0 +1,108 @@
+use std::io::Write;
+
+use crate::DynamicImage;
+
+// a <pre> block with a span for each pixel so it can be pasted into a web page
+pub(crate) fn write_html(img: &DynamicImage, out: &mut impl Write) -> std::io::Result<()> {
+    let img = img.to_rgb8();
+
+    out.write_all(b"<pre>\n")?;
+    for row in img.rows() {
+        for pixel in row {
+            write!(out, "<span style=\"background:#{:02x}{:02x}{:02x}\">&nbsp;</span>", pixel[0], pixel[1], pixel[2])?;
+        }
+        out.write_all(b"\n")?;
+    }
+    out.write_all(b"</pre>\n")
+}
+
+// one rect per run of same colored pixels in a row. each pixel is a square of pixel_size units
+pub(crate) fn write_svg(img: &DynamicImage, pixel_size: u32, out: &mut impl Write) -> std::io::Result<()> {
+    let img = img.to_rgb8();
+    let (width, height) = (img.width() * pixel_size, img.height() * pixel_size);
+
+    writeln!(out, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\" shape-rendering=\"crispEdges\">",
+             width, height, width, height)?;
+    for (y, row) in img.rows().enumerate() {
+        let row: Vec<_> = row.collect();
+
+        let mut start = 0;
+        while start < row.len() {
+            let end = (start..row.len()).find(|&x| row[x] != row[start]).unwrap_or(row.len());
+            let color = row[start];
+            writeln!(out, "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"#{:02x}{:02x}{:02x}\"/>",
+                     start as u32 * pixel_size, y as u32 * pixel_size, (end - start) as u32 * pixel_size, pixel_size,
+                     color[0], color[1], color[2])?;
+            start = end;
+        }
+    }
+    out.write_all(b"</svg>\n")
+}
+
+// {"width": W, "height": H, "pixels": [[r, g, b, a], ...]} with the pixels in rows from the top left
+pub(crate) fn write_json(img: &DynamicImage, out: &mut impl Write) -> std::io::Result<()> {
+    let img = img.to_rgba8();
+
+    write!(out, "{{\"width\": {}, \"height\": {}, \"pixels\": [", img.width(), img.height())?;
+    for (i, pixel) in img.pixels().enumerate() {
+        if i > 0 {
+            out.write_all(b", ")?;
+        }
+        write!(out, "[{}, {}, {}, {}]", pixel[0], pixel[1], pixel[2], pixel[3])?;
+    }
+    out.write_all(b"]}\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_span_per_pixel() {
+        let img = DynamicImage::new_rgb8(2, 2);
+        let mut out = Vec::new();
+        write_html(&img, &mut out).unwrap();
+
+        let html = String::from_utf8(out).unwrap();
+        assert_eq!(html.matches("<span").count(), 4);
+        assert!(html.starts_with("<pre>\n") && html.ends_with("</pre>\n"));
+    }
+
+    #[test]
+    fn a_rect_per_run() {
+        let img = DynamicImage::ImageRgb8(image::RgbImage::from_pixel(3, 2, image::Rgb([255, 0, 0])));
+        let mut out = Vec::new();
+        write_svg(&img, 10, &mut out).unwrap();
+
+        let svg = String::from_utf8(out).unwrap();
+        assert!(svg.contains("viewBox=\"0 0 30 20\""));
+        assert_eq!(svg.matches("<rect").count(), 2);
+        assert!(svg.contains("<rect x=\"0\" y=\"10\" width=\"30\" height=\"10\" fill=\"#ff0000\"/>"));
+
+        // a different color starts a new run
+        let mut img = img.to_rgb8();
+        img.put_pixel(2, 0, image::Rgb([0, 0, 255]));
+        let mut out = Vec::new();
+        write_svg(&DynamicImage::ImageRgb8(img), 10, &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap().matches("<rect").count(), 3);
+    }
+
+    #[test]
+    fn json_has_every_pixel() {
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::from_fn(3, 2, |x, y| image::Rgba([x as u8, y as u8, 7, 255])));
+        let mut out = Vec::new();
+        write_json(&img, &mut out).unwrap();
+
+        // there's no JSON parser to lean on so the document is taken apart by its shape
+        let json = String::from_utf8(out).unwrap();
+        let pixels = json.strip_prefix("{\"width\": 3, \"height\": 2, \"pixels\": [").unwrap()
+            .strip_suffix("]}\n").unwrap();
+        let pixels: Vec<Vec<u8>> = pixels.strip_prefix('[').unwrap().strip_suffix(']').unwrap()
+            .split("], [")
+            .map(|pixel| pixel.split(", ").map(|channel| channel.parse().unwrap()).collect())
+            .collect();
+        assert_eq!(pixels.len(), 3 * 2);
+        assert_eq!(pixels[0], [0, 0, 7, 255]);
+        assert_eq!(pixels[5], [2, 1, 7, 255]);
+    }
+}
@@ -0,0 +1,141 @@
+use std::io::Write;
+
+use image::GenericImageView;
+
+use crate::DynamicImage;
+
+// xterm refuses to draw sixel images larger than this by default
+static SIXEL_MAX_SIZE: u32 = 1000;
+
+pub(crate) fn write_sixel(img: &DynamicImage, out: &mut impl Write) -> Result<(), String> {
+    if img.width() > SIXEL_MAX_SIZE || img.height() > SIXEL_MAX_SIZE {
+        return Err(format!("{}x{} is too large for sixel. Use --size to render at most {}x{} pixels",
+                           img.width(), img.height(), SIXEL_MAX_SIZE, SIXEL_MAX_SIZE));
+    }
+
+    let img = img.to_rgba8();
+    let (width, height) = (img.width() as usize, img.height() as usize);
+
+    let quant = color_quant::NeuQuant::new(10, 256, &img);
+    let palette = quant.color_map_rgb();
+    let indices: Vec<usize> = img.pixels().map(|pixel| quant.index_of(&pixel.0)).collect();
+
+    // enter sixel mode and set the aspect ratio to 1:1 and the image size
+    let mut sixel = format!("\x1bPq\"1;1;{};{}", width, height);
+
+    // sixel colors are RGB percentages
+    for (i, color) in palette.chunks(3).enumerate() {
+        let percents: Vec<u32> = color.iter().map(|&c| (u32::from(c) * 100 + 127) / 255).collect();
+        sixel.push_str(&format!("#{};2;{};{};{}", i, percents[0], percents[1], percents[2]));
+    }
+
+    // each sixel is a column of 6 pixels, drawn once for every color in the band
+    for band in (0..height).step_by(6) {
+        let rows = 6.min(height - band);
+
+        let mut used = vec![false; palette.len() / 3];
+        for y in band..band + rows {
+            for &index in &indices[y * width..(y + 1) * width] {
+                used[index] = true;
+            }
+        }
+
+        for color in (0..used.len()).filter(|&color| used[color]) {
+            sixel.push_str(&format!("#{}", color));
+
+            let columns: Vec<char> = (0..width)
+                .map(|x| {
+                    let bits = (0..rows)
+                        .filter(|&dy| indices[(band + dy) * width + x] == color)
+                        .fold(0, |bits, dy| bits | 1 << dy);
+                    std::char::from_u32(63 + bits).unwrap()
+                })
+                .collect();
+
+            // compress repeated sixels as !<count><sixel>
+            let mut x = 0;
+            while x < columns.len() {
+                let run = columns[x..].iter().take_while(|&&c| c == columns[x]).count();
+                if run > 3 {
+                    sixel.push_str(&format!("!{}{}", run, columns[x]));
+                } else {
+                    (0..run).for_each(|_| sixel.push(columns[x]));
+                }
+                x += run;
+            }
+
+            // return to the start of the band to draw the next color
+            sixel.push('$');
+        }
+
+        // move down to the next band
+        sixel.push('-');
+    }
+
+    sixel.push_str("\x1b\\\n");
+    out.write_all(sixel.as_bytes()).map_err(|e| e.to_string())
+}
+
+static BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+
+    for chunk in data.chunks(3) {
+        let bytes = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = u32::from(bytes[0]) << 16 | u32::from(bytes[1]) << 8 | u32::from(bytes[2]);
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64_ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+// kitty accepts at most 4096 bytes of encoded data per escape
+static KITTY_CHUNK_SIZE: usize = 4096;
+
+pub(crate) fn write_kitty(img: &DynamicImage, out: &mut impl Write) -> std::io::Result<()> {
+    let img = img.to_rgba8();
+    let data = base64(&img);
+    let chunks: Vec<&[u8]> = data.as_bytes().chunks(KITTY_CHUNK_SIZE).collect();
+
+    for (i, chunk) in chunks.iter().enumerate() {
+        // m=1 means more chunks follow
+        let more = if i + 1 < chunks.len() { 1 } else { 0 };
+
+        if i == 0 {
+            // f=32 is 8 bit RGBA and a=T transmits and displays the image
+            out.write_all(format!("\x1b_Gf=32,s={},v={},a=T,m={};", img.width(), img.height(), more).as_bytes())?;
+        } else {
+            out.write_all(format!("\x1b_Gm={};", more).as_bytes())?;
+        }
+
+        out.write_all(chunk)?;
+        out.write_all(b"\x1b\\")?;
+    }
+
+    out.write_all(b"\n")
+}
+
+// stretch draws the image at exactly size instead of fitting it inside
+pub(crate) fn write_iterm(img: &DynamicImage, size: Option<(u32, u32)>, stretch: bool, out: &mut impl Write) -> Result<(), String> {
+    let mut png = Vec::new();
+    if let Err(e) = img.write_to(&mut png, image::ImageOutputFormat::Png) {
+        return Err(e.to_string());
+    }
+
+    let mut args = format!("inline=1;size={}", png.len());
+
+    // width and height are in cells and the image is fit inside them
+    if let Some(size) = size {
+        args.push_str(&format!(";width={};height={};preserveAspectRatio={}", size.0, size.1, if stretch { 0 } else { 1 }));
+    }
+
+    out.write_all(format!("\x1b]1337;File={}:{}\x07\n", args, base64(&png)).as_bytes()).map_err(|e| e.to_string())
+}
@@ -0,0 +1,284 @@
+use crate::color::from_linear;
+use crate::DynamicImage;
+
+// XYZ with a D50 white point to linear sRGB. ICC profiles use D50 so this is sRGB's matrix adapted to D50 with
+// Bradford and inverted
+static XYZ_TO_SRGB: [[f32; 3]; 3] = [
+    [3.133_856, -1.616_867, -0.490_615],
+    [-0.978_768, 1.916_142, 0.033_454],
+    [0.071_945, -0.228_991, 1.405_243]
+];
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset + 2).map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4).map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+// s15Fixed16Number
+fn fixed_at(data: &[u8], offset: usize) -> Option<f32> {
+    u32_at(data, offset).map(|value| value as i32 as f32 / 65536.0)
+}
+
+// the ICC profile embedded in a PNG or JPEG file, if it has one
+pub(crate) fn embedded_profile(file: &[u8]) -> Option<Vec<u8>> {
+    if file.starts_with(b"\x89PNG\r\n\x1a\n") {
+        png_profile(file)
+    } else if file.starts_with(&[0xff, 0xd8]) {
+        jpeg_profile(file)
+    } else {
+        None
+    }
+}
+
+// the iCCP chunk is the profile's name, a null, a compression method that is always 0, and the zlib compressed
+// profile. it has to come before the image data
+fn png_profile(file: &[u8]) -> Option<Vec<u8>> {
+    let mut offset = 8;
+    loop {
+        let length = u32_at(file, offset)? as usize;
+        let kind = file.get(offset + 4..offset + 8)?;
+        let data = file.get(offset + 8..offset + 8 + length)?;
+        match kind {
+            b"iCCP" => {
+                let name = data.iter().position(|&b| b == 0)?;
+                return miniz_oxide::inflate::decompress_to_vec_zlib(data.get(name + 2..)?).ok();
+            }
+            b"IDAT" | b"IEND" => return None,
+            _ => offset += length + 12
+        }
+    }
+}
+
+// large profiles are split across APP2 segments that start with ICC_PROFILE, a null, the segment's number from 1,
+// and the number of segments
+fn jpeg_profile(file: &[u8]) -> Option<Vec<u8>> {
+    let mut chunks = Vec::new();
+    let mut offset = 2;
+    // segments are only read up to the start of the image data
+    while let (Some(0xff), Some(&marker)) = (file.get(offset), file.get(offset + 1)) {
+        if marker == 0xda || marker == 0xd9 {
+            break;
+        }
+        let length = usize::from(u16_at(file, offset + 2)?);
+        let data = file.get(offset + 4..offset + 2 + length)?;
+        if marker == 0xe2 && data.starts_with(b"ICC_PROFILE\0") && data.len() > 14 {
+            chunks.push((data[12], &data[14..]));
+        }
+        offset += 2 + length;
+    }
+
+    if chunks.is_empty() {
+        return None;
+    }
+    chunks.sort_by_key(|&(number, _)| number);
+    Some(chunks.into_iter().flat_map(|(_, chunk)| chunk.iter().copied()).collect())
+}
+
+// a tone curve from a curv or para tag, sampled for every value of a channel with this many values. values are
+// linear light from 0 to 1
+fn curve(profile: &[u8], offset: usize, samples: usize) -> Option<Vec<f32>> {
+    let inputs = (0..samples).map(|value| value as f32 / (samples - 1) as f32);
+    match profile.get(offset..offset + 4)? {
+        b"curv" => {
+            let count = u32_at(profile, offset + 8)? as usize;
+            let entry = |i: usize| u16_at(profile, offset + 12 + i * 2).map(|value| f32::from(value) / 65535.0);
+            match count {
+                0 => Some(inputs.collect()),
+                // one entry is a gamma as a u8Fixed8Number
+                1 => {
+                    let gamma = f32::from(u16_at(profile, offset + 12)?) / 256.0;
+                    Some(inputs.map(|x| x.powf(gamma)).collect())
+                }
+                // other curves are tables spread evenly from 0 to 1 and interpolated between
+                _ => {
+                    let table = (0..count).map(entry).collect::<Option<Vec<_>>>()?;
+                    Some(inputs.map(|x| {
+                        let position = x * (count - 1) as f32;
+                        let i = (position as usize).min(count - 2);
+                        table[i] + (table[i + 1] - table[i]) * (position - i as f32)
+                    }).collect())
+                }
+            }
+        }
+        b"para" => {
+            let kind = u16_at(profile, offset + 8)?;
+            let count = [1, 3, 4, 5, 7].get(usize::from(kind))?;
+            let params = (0..*count).map(|i| fixed_at(profile, offset + 12 + i * 4)).collect::<Option<Vec<_>>>()?;
+            let g = params[0];
+            Some(inputs.map(|x| match *params.as_slice() {
+                [_] => x.powf(g),
+                [_, a, b] => if x >= -b / a { (a * x + b).powf(g) } else { 0.0 },
+                [_, a, b, c] => if x >= -b / a { (a * x + b).powf(g) + c } else { c },
+                [_, a, b, c, d] => if x >= d { (a * x + b).powf(g) } else { c * x },
+                [_, a, b, c, d, e, f] => if x >= d { (a * x + b).powf(g) + e } else { c * x + f },
+                _ => x
+            }).collect())
+        }
+        _ => None
+    }
+}
+
+// an RGB matrix profile. matrix takes linear colors in the profile to linear sRGB and curves are the tone curves of
+// red, green, and blue
+struct MatrixProfile {
+    matrix: [[f32; 3]; 3],
+    curves: Vec<Vec<f32>>
+}
+
+// None if the profile isn't an RGB matrix profile, like the LUT based profiles of printers. the curves have an entry
+// for each of samples values
+fn matrix_profile(profile: &[u8], samples: usize) -> Option<MatrixProfile> {
+    if profile.get(16..20)? != b"RGB " {
+        return None;
+    }
+
+    let tag = |signature: &[u8]| -> Option<usize> {
+        let count = u32_at(profile, 128)? as usize;
+        (0..count).map(|i| 132 + i * 12)
+            .find(|&entry| profile.get(entry..entry + 4) == Some(signature))
+            .and_then(|entry| u32_at(profile, entry + 4))
+            .map(|offset| offset as usize)
+    };
+
+    // each column is the XYZ of a primary
+    let mut matrix = [[0.0; 3]; 3];
+    let mut curves = Vec::new();
+    for (i, (xyz, trc)) in [(b"rXYZ", b"rTRC"), (b"gXYZ", b"gTRC"), (b"bXYZ", b"bTRC")].iter().enumerate() {
+        let offset = tag(*xyz)?;
+        for (row, value) in matrix.iter_mut().enumerate() {
+            value[i] = fixed_at(profile, offset + 8 + row * 4)?;
+        }
+        curves.push(curve(profile, tag(*trc)?, samples)?);
+    }
+
+    // profile RGB to XYZ to sRGB in one step
+    let mut combined = [[0.0; 3]; 3];
+    for (row, combined) in combined.iter_mut().enumerate() {
+        for (column, value) in combined.iter_mut().enumerate() {
+            *value = (0..3).map(|i| XYZ_TO_SRGB[row][i] * matrix[i][column]).sum();
+        }
+    }
+    Some(MatrixProfile { matrix: combined, curves })
+}
+
+// whether to_srgb can convert from a profile
+pub(crate) fn supported(profile: &[u8]) -> bool {
+    matrix_profile(profile, 2).is_some()
+}
+
+// converts an image from an RGB matrix profile, like Display P3 or Adobe RGB, to sRGB. None if the profile isn't
+// one of those. images with more than 8 bits a channel stay 16 bit
+pub(crate) fn to_srgb(img: &DynamicImage, profile: &[u8]) -> Option<DynamicImage> {
+    let color = img.color();
+    if color.bits_per_pixel() <= u16::from(color.channel_count()) * 8 {
+        let MatrixProfile { matrix, curves } = matrix_profile(profile, 256)?;
+        let mut img = img.to_rgba8();
+        for pixel in img.pixels_mut() {
+            let linear = [curves[0][usize::from(pixel[0])], curves[1][usize::from(pixel[1])], curves[2][usize::from(pixel[2])]];
+            for (i, row) in matrix.iter().enumerate() {
+                pixel[i] = (from_linear(row[0] * linear[0] + row[1] * linear[1] + row[2] * linear[2]) * 255.0).round() as u8;
+            }
+        }
+        return Some(DynamicImage::ImageRgba8(img));
+    }
+
+    let MatrixProfile { matrix, curves } = matrix_profile(profile, 65536)?;
+    let mut img = img.to_rgba16();
+    for pixel in img.pixels_mut() {
+        let linear = [curves[0][usize::from(pixel[0])], curves[1][usize::from(pixel[1])], curves[2][usize::from(pixel[2])]];
+        for (i, row) in matrix.iter().enumerate() {
+            pixel[i] = (from_linear(row[0] * linear[0] + row[1] * linear[1] + row[2] * linear[2]) * 65535.0).round() as u16;
+        }
+    }
+    Some(DynamicImage::ImageRgba16(img))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixed(value: f32) -> [u8; 4] {
+        ((value * 65536.0).round() as i32).to_be_bytes()
+    }
+
+    // a Display P3 profile with the primaries adapted to D50 and the sRGB tone curve, like Apple's
+    fn display_p3() -> Vec<u8> {
+        let mut profile = vec![0; 128];
+        profile[16..20].copy_from_slice(b"RGB ");
+
+        let primaries: [(&[u8], [f32; 3]); 3] = [
+            (b"rXYZ", [0.515_1, 0.241_2, -0.001_1]),
+            (b"gXYZ", [0.292_0, 0.692_2, 0.041_9]),
+            (b"bXYZ", [0.157_1, 0.066_6, 0.784_1])
+        ];
+        let curves: [&[u8]; 3] = [b"rTRC", b"gTRC", b"bTRC"];
+
+        // 3 XYZ tags of 20 bytes then one para tag the curves share
+        let start = 132 + 6 * 12;
+        profile.extend_from_slice(&6u32.to_be_bytes());
+        for (i, (signature, _)) in primaries.iter().enumerate() {
+            profile.extend_from_slice(signature);
+            profile.extend_from_slice(&(start + i as u32 * 20).to_be_bytes());
+            profile.extend_from_slice(&20u32.to_be_bytes());
+        }
+        for signature in &curves {
+            profile.extend_from_slice(signature);
+            profile.extend_from_slice(&(start + 60).to_be_bytes());
+            profile.extend_from_slice(&32u32.to_be_bytes());
+        }
+
+        for (_, xyz) in &primaries {
+            profile.extend_from_slice(b"XYZ \0\0\0\0");
+            for &value in xyz {
+                profile.extend_from_slice(&fixed(value));
+            }
+        }
+        profile.extend_from_slice(b"para\0\0\0\0\0\x03\0\0");
+        for &value in &[2.4, 1.0 / 1.055, 0.055 / 1.055, 1.0 / 12.92, 0.040_45] {
+            profile.extend_from_slice(&fixed(value));
+        }
+        profile
+    }
+
+    #[test]
+    fn display_p3_to_srgb() {
+        let img = DynamicImage::ImageRgb8(image::RgbImage::from_pixel(1, 1, image::Rgb([234, 51, 35])));
+        let converted = to_srgb(&img, &display_p3()).unwrap().to_rgba8();
+        let pixel = converted.get_pixel(0, 0);
+
+        // sRGB red is just inside P3
+        for (channel, expected) in pixel.0.iter().zip(&[255, 0, 0, 255]) {
+            assert!((i32::from(*channel) - expected).abs() <= 1, "{:?}", pixel);
+        }
+    }
+
+    #[test]
+    fn deep_images_stay_16_bit() {
+        // both of these are 4 in 8 bits
+        let img = image::ImageBuffer::from_fn(2, 1, |x, _| image::Rgb([[1000, 1100][x as usize]; 3]));
+        let converted = to_srgb(&DynamicImage::ImageRgb16(img), &display_p3()).unwrap();
+
+        let converted = match converted {
+            DynamicImage::ImageRgba16(converted) => converted,
+            _ => panic!("{:?} isn't 16 bit", converted.color())
+        };
+        let (dark, light) = (converted.get_pixel(0, 0), converted.get_pixel(1, 0));
+        // P3 and sRGB share a white point and tone curve so grays stay the same
+        for (channel, expected) in dark.0.iter().zip(&[1000, 1000, 1000, 65535]) {
+            assert!((i32::from(*channel) - expected).abs() <= 16, "{:?}", dark);
+        }
+        assert!(light[0] > dark[0] + 50);
+    }
+
+    #[test]
+    fn lut_profiles_are_unsupported() {
+        let mut profile = display_p3();
+        // none of the matrix tags, like a profile made of A2B0 tables
+        profile[128..132].copy_from_slice(&0u32.to_be_bytes());
+        assert!(!supported(&profile));
+        assert!(supported(&display_p3()));
+    }
+}
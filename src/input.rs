@@ -0,0 +1,624 @@
+use std::fs::File;
+use std::io::{stdin, BufRead, BufReader, Cursor, Read, Seek};
+use std::time::Duration;
+
+use image::{AnimationDecoder, ColorType, GenericImageView, ImageError, ImageFormat, Pixel};
+
+#[cfg(feature = "color-management")]
+use crate::icc;
+use crate::DynamicImage;
+
+// "-" reads the input from stdin and http:// or https:// downloads it
+fn read_input(input: &str, timeout: Duration) -> Result<Vec<u8>, String> {
+    if input == "-" {
+        let mut buffer = Vec::new();
+        if let Err(e) = stdin().read_to_end(&mut buffer) {
+            return Err(e.to_string());
+        }
+        return Ok(buffer);
+    }
+
+    if is_url(input) {
+        return fetch(input, timeout);
+    }
+
+    std::fs::read(input).map_err(|e| e.to_string())
+}
+
+fn is_url(input: &str) -> bool {
+    input.starts_with("http://") || input.starts_with("https://")
+}
+
+// formats that are only decoded when the crate is built with the feature of the same name
+fn missing_feature(format: ImageFormat) -> Option<&'static str> {
+    match format {
+        ImageFormat::WebP if cfg!(not(feature = "webp")) => Some("webp"),
+        ImageFormat::Avif if cfg!(not(feature = "avif")) => Some("avif"),
+        _ => None
+    }
+}
+
+// format is the format the input looks like
+fn decode_error(e: ImageError, format: Option<ImageFormat>) -> String {
+    match format.and_then(missing_feature) {
+        Some(feature) => format!("{}. Build with --features {} to read these images", e, feature),
+        None => e.to_string()
+    }
+}
+
+// EXIF orientation from 1 to 8. images without one are upright
+fn exif_orientation(container: &mut (impl BufRead + Seek)) -> u32 {
+    exif::Reader::new().read_from_container(container).ok()
+        .and_then(|exif| exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY).and_then(|field| field.value.get_uint(0)))
+        .unwrap_or(1)
+}
+
+// rotates and flips an image so it's upright based on its EXIF orientation
+fn orient(img: DynamicImage, orientation: u32) -> DynamicImage {
+    match orientation {
+        2 => img.fliph(),
+        3 => img.rotate180(),
+        4 => img.flipv(),
+        5 => img.rotate90().fliph(),
+        6 => img.rotate90(),
+        7 => img.rotate270().fliph(),
+        8 => img.rotate270(),
+        _ => img
+    }
+}
+
+// converts an image to sRGB from the ICC profile embedded in its file. images without one, or with a profile that
+// can't be converted, are drawn as if they're already sRGB
+#[cfg(feature = "color-management")]
+fn color_manage(img: DynamicImage, file: &[u8]) -> DynamicImage {
+    match icc::embedded_profile(file).and_then(|profile| icc::to_srgb(&img, &profile)) {
+        Some(converted) => converted,
+        None => img
+    }
+}
+
+#[cfg(not(feature = "color-management"))]
+fn color_manage(img: DynamicImage, _file: &[u8]) -> DynamicImage {
+    img
+}
+
+/// Whether the file at input has an embedded ICC profile that `load_image` can't convert to sRGB, like the LUT based
+/// profiles of printers, so it's drawn as if it's already sRGB. Always false for stdin and URLs, and for every file
+/// without the color-management feature
+#[cfg(feature = "color-management")]
+pub fn unconverted_profile(input: &str) -> bool {
+    if input == "-" || is_url(input) {
+        return false;
+    }
+    let profile = std::fs::read(input).ok().and_then(|file| icc::embedded_profile(&file));
+    profile.is_some_and(|profile| !icc::supported(&profile))
+}
+
+#[cfg(not(feature = "color-management"))]
+pub fn unconverted_profile(_input: &str) -> bool {
+    false
+}
+
+// applies the ICC profile and EXIF orientation of the file an image was decoded from when asked to
+fn correct(img: DynamicImage, file: &[u8], auto_orient: bool, color_managed: bool) -> DynamicImage {
+    let img = if color_managed { color_manage(img, file) } else { img };
+    if !auto_orient {
+        return img;
+    }
+    orient(img, exif_orientation(&mut Cursor::new(file)))
+}
+
+fn decode(buffer: &[u8], auto_orient: bool, color_managed: bool) -> Result<DynamicImage, String> {
+    let img = image::load_from_memory(buffer).map_err(|e| decode_error(e, image::guess_format(buffer).ok()))?;
+    Ok(correct(img, buffer, auto_orient, color_managed))
+}
+
+/// Opens an image from a path, a URL, or stdin when the input is "-". auto_orient applies the EXIF orientation and
+/// color_managed converts the image to sRGB from its embedded ICC profile
+pub fn load_image(input: &str, timeout: Duration, auto_orient: bool, color_managed: bool)
+    -> Result<DynamicImage, String> {
+    // files are opened by path so formats without a signature can be guessed from the extension
+    if input == "-" || is_url(input) {
+        return decode(&read_input(input, timeout)?, auto_orient, color_managed);
+    }
+
+    let mut img = image::open(input).map_err(|e| decode_error(e, ImageFormat::from_path(input).ok()))?;
+    if color_managed {
+        img = color_manage(img, &std::fs::read(input).map_err(|e| e.to_string())?);
+    }
+    if !auto_orient {
+        return Ok(img);
+    }
+    let orientation = File::open(input).map_or(1, |file| exif_orientation(&mut BufReader::new(file)));
+    Ok(orient(img, orientation))
+}
+
+// decodes a PNG a row at a time and averages each square of factor by factor pixels into one pixel so the whole
+// image is never in memory. rows of every bit depth come out as 8 bits, so 16 bit PNGs lose their low bits and
+// smaller depths and palettes are expanded. None if the PNG is interlaced, since then rows don't come out in order
+fn decode_png_shrunk(reader: impl Read, factor: u32) -> Result<Option<DynamicImage>, String> {
+    let mut decoder = png::Decoder::new(reader);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let (info, mut reader) = decoder.read_info().map_err(|e| e.to_string())?;
+    let (color, depth) = reader.output_color_type();
+    if reader.info().interlaced || depth != png::BitDepth::Eight {
+        return Ok(None);
+    }
+
+    let channels = color.samples();
+    let (width, height) = (info.width.div_ceil(factor), info.height.div_ceil(factor));
+    let mut img = image::RgbaImage::new(width, height);
+    // the sum of every channel of each pixel in the row being shrunk and how many pixels went into it
+    let mut sums = vec![[0u32; 4]; width as usize];
+    let mut counts = vec![0u32; width as usize];
+
+    for y in 0..info.height {
+        let row = match reader.next_row().map_err(|e| e.to_string())? {
+            Some(row) => row,
+            None => break
+        };
+
+        for (x, pixel) in row.chunks_exact(channels).enumerate() {
+            // gray is spread to every color channel and missing alpha is opaque
+            let rgba = match *pixel {
+                [gray] => [gray, gray, gray, 255],
+                [gray, alpha] => [gray, gray, gray, alpha],
+                [r, g, b] => [r, g, b, 255],
+                [r, g, b, alpha] => [r, g, b, alpha],
+                _ => return Ok(None)
+            };
+            let x = x / factor as usize;
+            for i in 0..4 {
+                sums[x][i] += u32::from(rgba[i]);
+            }
+            counts[x] += 1;
+        }
+
+        if (y + 1) % factor == 0 || y + 1 == info.height {
+            for x in 0..width as usize {
+                let count = counts[x].max(1);
+                let channel = |i: usize| ((sums[x][i] + count / 2) / count) as u8;
+                img.put_pixel(x as u32, y / factor, image::Rgba([channel(0), channel(1), channel(2), channel(3)]));
+            }
+            sums.iter_mut().for_each(|sum| *sum = [0; 4]);
+            counts.iter_mut().for_each(|count| *count = 0);
+        }
+    }
+    Ok(Some(DynamicImage::ImageRgba8(img)))
+}
+
+// --stream looks for an ICC profile in this much of the start of a PNG
+static PROFILE_SEARCH_BYTES: u64 = 1 << 20;
+
+/// Opens an image like `load_image` but shrinks PNGs while they're decoded so it's never in memory at full size.
+/// at_least is the smallest size in pixels the image can shrink to. Other formats are decoded normally
+pub fn load_image_streamed(input: &str, timeout: Duration, auto_orient: bool, color_managed: bool,
+                           at_least: (u32, u32)) -> Result<DynamicImage, String> {
+    // only the header is read to find the size before deciding how much to shrink
+    let buffer = if input == "-" || is_url(input) {
+        Some(read_input(input, timeout)?)
+    } else {
+        None
+    };
+    let open = || -> Result<Box<dyn BufRead>, String> {
+        match &buffer {
+            Some(buffer) => Ok(Box::new(Cursor::new(buffer.as_slice()))),
+            None => match File::open(input) {
+                Ok(file) => Ok(Box::new(BufReader::new(file))),
+                Err(e) => Err(e.to_string())
+            }
+        }
+    };
+
+    let mut header = [0; 24];
+    let is_png = open()?.read_exact(&mut header).is_ok() && image::guess_format(&header).ok() == Some(ImageFormat::Png);
+    // the size is at the start of the first chunk, right after the signature
+    let size = (u32::from_be_bytes([header[16], header[17], header[18], header[19]]),
+                u32::from_be_bytes([header[20], header[21], header[22], header[23]]));
+    // the image can be rotated so its shorter side must still cover the longer side of the target
+    let factor = size.0.min(size.1) / at_least.0.max(at_least.1).max(1);
+
+    if is_png && factor > 1 {
+        if let Some(mut img) = decode_png_shrunk(open()?, factor)? {
+            // the profile is at the start of the file so only that much is read again
+            if color_managed {
+                let mut start = Vec::new();
+                open()?.take(PROFILE_SEARCH_BYTES).read_to_end(&mut start).map_err(|e| e.to_string())?;
+                img = color_manage(img, &start);
+            }
+            if !auto_orient {
+                return Ok(img);
+            }
+            let orientation = match &buffer {
+                Some(buffer) => exif_orientation(&mut Cursor::new(buffer.as_slice())),
+                None => File::open(input).map_or(1, |file| exif_orientation(&mut BufReader::new(file)))
+            };
+            return Ok(orient(img, orientation));
+        }
+    }
+
+    match buffer {
+        Some(buffer) => decode(&buffer, auto_orient, color_managed),
+        None => load_image(input, timeout, auto_orient, color_managed)
+    }
+}
+
+// GIFs frames that don't set a delay are shown for this long like in browsers
+static DEFAULT_FRAME_DELAY: Duration = Duration::from_millis(100);
+
+/// Decodes every frame of an animated GIF or PNG along with how long each is shown. Other images are one frame
+pub fn load_frames(input: &str, timeout: Duration, auto_orient: bool, color_managed: bool)
+    -> Result<Vec<(DynamicImage, Duration)>, String> {
+    let buffer = read_input(input, timeout)?;
+
+    let format = image::guess_format(&buffer).ok();
+    if format == Some(ImageFormat::Png) {
+        if let Some(frames) = apng_frames(&buffer)? {
+            let correct = |(img, delay)| (correct(img, &buffer, auto_orient, color_managed), delay);
+            return Ok(frames.into_iter().map(correct).collect());
+        }
+    }
+
+    if format != Some(ImageFormat::Gif) {
+        let img = if input == "-" || is_url(input) {
+            decode(&buffer, auto_orient, color_managed)
+        } else {
+            load_image(input, timeout, auto_orient, color_managed)
+        };
+        return img.map(|img| vec![(img, Duration::from_millis(0))]);
+    }
+
+    let decoder = match image::codecs::gif::GifDecoder::new(Cursor::new(buffer)) {
+        Ok(decoder) => decoder,
+        Err(e) => return Err(e.to_string())
+    };
+
+    let mut frames = Vec::new();
+    for frame in decoder.into_frames() {
+        let frame = frame.map_err(|e| e.to_string())?;
+        let delay = match Duration::from(frame.delay()) {
+            delay if delay == Duration::from_millis(0) => DEFAULT_FRAME_DELAY,
+            delay => delay
+        };
+        frames.push((DynamicImage::ImageRgba8(frame.into_buffer()), delay));
+    }
+    Ok(frames)
+}
+
+// draws every frame of an animated PNG onto a canvas the size of the image. None if the PNG isn't animated, so it's
+// loaded like any other image
+fn apng_frames(buffer: &[u8]) -> Result<Option<Vec<(DynamicImage, Duration)>>, String> {
+    let mut decoder = png::Decoder::new(buffer);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let (info, mut reader) = decoder.read_info().map_err(|e| e.to_string())?;
+    let count = match reader.info().animation_control {
+        Some(control) if control.num_frames > 1 => control.num_frames,
+        _ => return Ok(None)
+    };
+
+    let mut data = vec![0; reader.output_buffer_size()];
+    // an image without a frame control before it is a still shown by viewers that can't animate
+    if reader.info().frame_control.is_none() {
+        reader.next_frame(&mut data).map_err(|e| e.to_string())?;
+    }
+
+    let channels = info.line_size / info.width as usize;
+    let mut canvas = image::RgbaImage::new(info.width, info.height);
+    let mut frames = Vec::new();
+    for _ in 0..count {
+        reader.next_frame(&mut data).map_err(|e| e.to_string())?;
+        let control = reader.info().frame_control.ok_or("An animated PNG frame is missing its frame control")?;
+        let (x, y, width, height) = (control.x_offset, control.y_offset, control.width, control.height);
+        if x + width > info.width || y + height > info.height {
+            return Err("An animated PNG frame goes past the edge of the image".to_string());
+        }
+
+        // previous puts back what was under the frame once it's been shown. there's nothing under the first frame so
+        // it's cleared like background
+        let dispose = match control.dispose_op {
+            png::DisposeOp::Previous if frames.is_empty() => png::DisposeOp::Background,
+            dispose => dispose
+        };
+        let before = if dispose == png::DisposeOp::Previous { Some(canvas.clone()) } else { None };
+
+        for (i, pixel) in data.chunks_exact(channels).take((width * height) as usize).enumerate() {
+            let rgba = image::Rgba(match *pixel {
+                [gray] => [gray, gray, gray, 255],
+                [gray, alpha] => [gray, gray, gray, alpha],
+                [r, g, b] => [r, g, b, 255],
+                [r, g, b, a] => [r, g, b, a],
+                _ => return Err("An animated PNG frame has an unsupported color type".to_string())
+            });
+            let target = canvas.get_pixel_mut(x + i as u32 % width, y + i as u32 / width);
+            match control.blend_op {
+                png::BlendOp::Source => *target = rgba,
+                png::BlendOp::Over => target.blend(&rgba)
+            }
+        }
+
+        // delays are fractions of a second. a denominator of 0 means hundredths
+        let denominator = if control.delay_den == 0 { 100 } else { u64::from(control.delay_den) };
+        let delay = match Duration::from_millis(u64::from(control.delay_num) * 1000 / denominator) {
+            delay if delay == Duration::from_millis(0) => DEFAULT_FRAME_DELAY,
+            delay => delay
+        };
+        frames.push((DynamicImage::ImageRgba8(canvas.clone()), delay));
+
+        match (dispose, before) {
+            (png::DisposeOp::Background, _) => {
+                for y in y..y + height {
+                    for x in x..x + width {
+                        canvas.put_pixel(x, y, image::Rgba([0, 0, 0, 0]));
+                    }
+                }
+            }
+            (png::DisposeOp::Previous, Some(before)) => canvas = before,
+            _ => {}
+        }
+    }
+    Ok(Some(frames))
+}
+
+#[cfg(feature = "remote")]
+fn fetch(url: &str, timeout: Duration) -> Result<Vec<u8>, String> {
+    let response = match ureq::AgentBuilder::new().timeout(timeout).build().get(url).call() {
+        Ok(response) => response,
+        Err(e) => return Err(e.to_string())
+    };
+
+    let mut buffer = Vec::new();
+    if let Err(e) = response.into_reader().read_to_end(&mut buffer) {
+        return Err(e.to_string());
+    }
+    Ok(buffer)
+}
+
+#[cfg(not(feature = "remote"))]
+fn fetch(_url: &str, _timeout: Duration) -> Result<Vec<u8>, String> {
+    Err("Reading images from URLs requires building with --features remote".to_string())
+}
+
+/// What `image_info` found out about an image
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ImageInfo {
+    pub format: ImageFormat,
+    pub width: u32,
+    pub height: u32,
+    /// Color type the image decodes to
+    pub color: ColorType,
+    /// Bits in each channel of a pixel in the file
+    pub bit_depth: u8,
+    /// The image has more than one frame
+    pub animated: bool
+}
+
+// the color type from the header of an image. None for formats that have to be decoded to find it
+fn header_color(buffer: &[u8], format: ImageFormat) -> Option<ColorType> {
+    use image::codecs::*;
+    use image::ImageDecoder;
+
+    let cursor = Cursor::new(buffer);
+    match format {
+        ImageFormat::Png => png::PngDecoder::new(cursor).ok().map(|decoder| decoder.color_type()),
+        ImageFormat::Jpeg => jpeg::JpegDecoder::new(cursor).ok().map(|decoder| decoder.color_type()),
+        ImageFormat::Gif => gif::GifDecoder::new(cursor).ok().map(|decoder| decoder.color_type()),
+        ImageFormat::Bmp => bmp::BmpDecoder::new(cursor).ok().map(|decoder| decoder.color_type()),
+        ImageFormat::Ico => ico::IcoDecoder::new(cursor).ok().map(|decoder| decoder.color_type()),
+        ImageFormat::Tiff => tiff::TiffDecoder::new(cursor).ok().map(|decoder| decoder.color_type()),
+        ImageFormat::Tga => tga::TgaDecoder::new(cursor).ok().map(|decoder| decoder.color_type()),
+        ImageFormat::Pnm => pnm::PnmDecoder::new(cursor).ok().map(|decoder| decoder.color_type()),
+        ImageFormat::Farbfeld => farbfeld::FarbfeldDecoder::new(cursor).ok().map(|decoder| decoder.color_type()),
+        ImageFormat::Hdr => hdr::HdrAdapter::new(cursor).ok().map(|decoder| decoder.color_type()),
+        _ => None
+    }
+}
+
+/// Reads the format, size, color type, bit depth, and whether an image is animated from a path, a URL, or stdin
+/// when the input is "-". Only the header is decoded for most formats
+pub fn image_info(input: &str, timeout: Duration) -> Result<ImageInfo, String> {
+    let buffer = read_input(input, timeout)?;
+    let reader = image::io::Reader::new(Cursor::new(buffer.as_slice())).with_guessed_format().map_err(|e| e.to_string())?;
+    // formats without a signature are guessed from the extension
+    let format = match reader.format().map_or_else(|| ImageFormat::from_path(input).ok(), Some) {
+        Some(format) => format,
+        None => return Err("The format of the image couldn't be recognized".to_string())
+    };
+
+    let (width, height, color) = match header_color(&buffer, format) {
+        Some(color) => {
+            let mut reader = image::io::Reader::new(Cursor::new(buffer.as_slice()));
+            reader.set_format(format);
+            let (width, height) = reader.into_dimensions().map_err(|e| decode_error(e, Some(format)))?;
+            (width, height, color)
+        }
+        None => {
+            let img = image::load_from_memory_with_format(&buffer, format).map_err(|e| decode_error(e, Some(format)))?;
+            (img.width(), img.height(), img.color())
+        }
+    };
+    let mut bit_depth = (color.bits_per_pixel() / u16::from(color.channel_count())) as u8;
+
+    let animated = match format {
+        // decoded PNGs are always at least 8 bits so the real depth comes from the header
+        ImageFormat::Png => {
+            let (_, reader) = png::Decoder::new(buffer.as_slice()).read_info().map_err(|e| e.to_string())?;
+            bit_depth = reader.info().bit_depth as u8;
+            reader.info().animation_control.is_some_and(|control| control.num_frames > 1)
+        }
+        ImageFormat::Gif => match image::codecs::gif::GifDecoder::new(Cursor::new(buffer.as_slice())) {
+            Ok(decoder) => decoder.into_frames().take(2).count() > 1,
+            Err(_) => false
+        },
+        _ => false
+    };
+
+    Ok(ImageInfo { format, width, height, color, bit_depth, animated })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a stored image with a different value in each pixel
+    const W: u32 = 2;
+    const H: u32 = 3;
+
+    // where each pixel of the upright image comes from in the stored one
+    fn source(orientation: u32, x: u32, y: u32) -> (u32, u32) {
+        match orientation {
+            2 => (W - 1 - x, y),
+            3 => (W - 1 - x, H - 1 - y),
+            4 => (x, H - 1 - y),
+            // transpose
+            5 => (y, x),
+            6 => (y, H - 1 - x),
+            // transverse
+            7 => (W - 1 - y, H - 1 - x),
+            8 => (W - 1 - y, x),
+            _ => (x, y)
+        }
+    }
+
+    #[test]
+    fn orientations() {
+        let img = DynamicImage::ImageLuma8(image::GrayImage::from_fn(W, H, |x, y| image::Luma([(y * W + x) as u8])));
+
+        for orientation in 1..=8 {
+            let oriented = orient(img.clone(), orientation).to_luma8();
+            let size = if orientation >= 5 { (H, W) } else { (W, H) };
+            assert_eq!(oriented.dimensions(), size, "orientation {}", orientation);
+
+            for (x, y, pixel) in oriented.enumerate_pixels() {
+                let (sx, sy) = source(orientation, x, y);
+                assert_eq!(pixel.0[0], (sy * W + sx) as u8, "orientation {} at {},{}", orientation, x, y);
+            }
+        }
+    }
+
+    fn crc32(bytes: &[u8]) -> u32 {
+        let mut crc = !0u32;
+        for byte in bytes {
+            crc ^= u32::from(*byte);
+            for _ in 0..8 {
+                crc = if crc & 1 == 1 { 0xedb8_8320 ^ (crc >> 1) } else { crc >> 1 };
+            }
+        }
+        !crc
+    }
+
+    fn chunk(png: &mut Vec<u8>, kind: &[u8], data: &[u8]) {
+        png.extend(&(data.len() as u32).to_be_bytes());
+        let start = png.len();
+        png.extend(kind);
+        png.extend(data);
+        let crc = crc32(&png[start..]);
+        png.extend(&crc.to_be_bytes());
+    }
+
+    // an RGBA APNG with a full sized frame of each color and depth bits in each channel. the first frame is also the
+    // still image
+    fn apng(width: u32, height: u32, depth: u8, colors: &[[u8; 4]]) -> Vec<u8> {
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        let mut header = Vec::new();
+        header.extend(&width.to_be_bytes());
+        header.extend(&height.to_be_bytes());
+        header.extend(&[depth, 6, 0, 0, 0]);
+        chunk(&mut png, b"IHDR", &header);
+
+        let mut control = (colors.len() as u32).to_be_bytes().to_vec();
+        control.extend(&0u32.to_be_bytes());
+        chunk(&mut png, b"acTL", &control);
+
+        let mut sequence = 0u32;
+        for (i, color) in colors.iter().enumerate() {
+            let mut frame = sequence.to_be_bytes().to_vec();
+            sequence += 1;
+            for value in &[width, height, 0, 0] {
+                frame.extend(&value.to_be_bytes());
+            }
+            // a tenth of a second, disposed to the background of a transparent canvas
+            frame.extend(&[0, 1, 0, 10, 1, 0]);
+            chunk(&mut png, b"fcTL", &frame);
+
+            // 16 bit values are each byte twice, like 0xffff for 0xff
+            let samples: Vec<u8> = color.iter().flat_map(|&value| vec![value; usize::from(depth / 8)]).collect();
+            let rows: Vec<u8> = (0..height).flat_map(|_| {
+                std::iter::once(0).chain(samples.iter().copied().cycle().take(width as usize * samples.len()))
+            }).collect();
+            let data = miniz_oxide::deflate::compress_to_vec_zlib(&rows, 6);
+            if i == 0 {
+                chunk(&mut png, b"IDAT", &data);
+            } else {
+                let mut frame = sequence.to_be_bytes().to_vec();
+                sequence += 1;
+                frame.extend(data);
+                chunk(&mut png, b"fdAT", &frame);
+            }
+        }
+        chunk(&mut png, b"IEND", &[]);
+        png
+    }
+
+    #[test]
+    fn apng_frames_are_drawn() {
+        let colors = [[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255]];
+        for &depth in &[8, 16] {
+            let frames = apng_frames(&apng(3, 2, depth, &colors)).unwrap().unwrap();
+
+            assert_eq!(frames.len(), 3);
+            for ((img, delay), color) in frames.iter().zip(&colors) {
+                assert_eq!(img.dimensions(), (3, 2));
+                assert!(img.to_rgba8().pixels().all(|pixel| pixel.0 == *color), "{} bits", depth);
+                assert_eq!(*delay, Duration::from_millis(100));
+            }
+        }
+    }
+
+    fn encode_png(img: &DynamicImage) -> Vec<u8> {
+        let mut png = Vec::new();
+        img.write_to(&mut png, image::ImageOutputFormat::Png).unwrap();
+        png
+    }
+
+    #[test]
+    fn shrinking_while_decoding() {
+        // 16 bit and 1 bit PNGs come out of the decoder as 8 bits
+        let deep = DynamicImage::ImageLuma16(image::ImageBuffer::from_fn(8, 4, |x, _| image::Luma([if x < 4 { 0 } else { 65535 }])));
+        let shrunk = decode_png_shrunk(encode_png(&deep).as_slice(), 4).unwrap().unwrap().to_rgba8();
+        assert_eq!(shrunk.dimensions(), (2, 1));
+        assert_eq!(shrunk.get_pixel(0, 0).0, [0, 0, 0, 255]);
+        assert_eq!(shrunk.get_pixel(1, 0).0, [255, 255, 255, 255]);
+
+        let mut bits = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut bits, 8, 2);
+            encoder.set_color(png::ColorType::Grayscale);
+            encoder.set_depth(png::BitDepth::One);
+            // each row is one byte of 8 pixels
+            encoder.write_header().unwrap().write_image_data(&[0b1111_0000, 0b1111_0000]).unwrap();
+        }
+        let shrunk = decode_png_shrunk(bits.as_slice(), 2).unwrap().unwrap().to_rgba8();
+        assert_eq!(shrunk.dimensions(), (4, 1));
+        assert_eq!(shrunk.pixels().map(|pixel| pixel[0]).collect::<Vec<_>>(), [255, 255, 0, 0]);
+    }
+
+    #[test]
+    fn info_of_pngs() {
+        let dir = std::env::temp_dir().join(format!("image_render_info_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let info = |name: &str, png: Vec<u8>| {
+            let path = dir.join(name);
+            std::fs::write(&path, png).unwrap();
+            image_info(path.to_str().unwrap(), Duration::from_secs(1)).unwrap()
+        };
+
+        let still = info("still.png", encode_png(&DynamicImage::new_rgb8(30, 20)));
+        assert_eq!(still, ImageInfo {
+            format: ImageFormat::Png, width: 30, height: 20, color: ColorType::Rgb8, bit_depth: 8, animated: false
+        });
+        let animated = info("animated.png", apng(4, 2, 16, &[[255, 0, 0, 255], [0, 0, 255, 255]]));
+        assert_eq!((animated.width, animated.height, animated.color), (4, 2, ColorType::Rgba16));
+        assert_eq!((animated.bit_depth, animated.animated), (16, true));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,274 @@
+use std::io::Write;
+
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+// space put between images on the same row
+static SEPARATOR: &str = " ";
+
+// columns a line takes up on screen ignoring ESC[...m escapes
+fn visible_width(line: &str) -> usize {
+    let mut text = String::new();
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            chars.by_ref().find(|&c| c == 'm');
+        } else {
+            text.push(c);
+        }
+    }
+    UnicodeWidthStr::width(text.as_str())
+}
+
+/// Arranges images drawn by text modes into a grid `columns` wide. Every image is padded with blank cells to the
+/// size of the widest image and the tallest image in its row so the grid lines up
+pub fn montage(images: &[Vec<u8>], columns: usize) -> Vec<u8> {
+    let images: Vec<Vec<String>> = images.iter()
+        .map(|image| String::from_utf8_lossy(image).lines().map(String::from).collect())
+        .collect();
+    let width = images.iter().flatten().map(|line| visible_width(line)).max().unwrap_or(0);
+
+    let mut out = String::new();
+    for (i, row) in images.chunks(columns.max(1)).enumerate() {
+        // leave a blank line between rows like between separate images
+        if i > 0 {
+            out.push('\n');
+        }
+
+        let height = row.iter().map(Vec::len).max().unwrap_or(0);
+        for y in 0..height {
+            let line: Vec<String> = row.iter().map(|image| {
+                let line = image.get(y).map_or("", String::as_str);
+                format!("{}{}", line, " ".repeat(width - visible_width(line)))
+            }).collect();
+            out.push_str(&line.join(SEPARATOR));
+            out.push('\n');
+        }
+    }
+    out.into_bytes()
+}
+
+/// Writes `\r\n` in place of every `\n` written through it, for output read on Windows
+pub struct Crlf<W: Write>(pub W);
+
+impl<W: Write> Write for Crlf<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        for (i, line) in buf.split(|&b| b == b'\n').enumerate() {
+            if i > 0 {
+                self.0.write_all(b"\r\n")?;
+            }
+            self.0.write_all(line)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// Leaves out the last `\n` written through it so output can be embedded in a line of other text. A newline is held
+/// back until something else is written after it
+pub struct NoTrailingNewline<W: Write> {
+    out: W,
+    pending: bool
+}
+
+impl<W: Write> NoTrailingNewline<W> {
+    pub fn new(out: W) -> Self {
+        NoTrailingNewline { out, pending: false }
+    }
+}
+
+impl<W: Write> Write for NoTrailingNewline<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let (last, rest) = match buf.split_last() {
+            Some(split) => split,
+            None => return Ok(0)
+        };
+        if self.pending {
+            self.out.write_all(b"\n")?;
+        }
+        self.out.write_all(rest)?;
+        self.pending = *last == b'\n';
+        if !self.pending {
+            self.out.write_all(&[*last])?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.out.flush()
+    }
+}
+
+// spaces to the left of and lines above an image to put it in the middle of the terminal. images that don't fit
+// aren't padded in that direction
+pub(crate) fn center_padding(image: (u32, u32), terminal: (u32, u32)) -> (u32, u32) {
+    // like the term size, leave a line for the prompt
+    let rows = terminal.1.saturating_sub(1);
+    (terminal.0.saturating_sub(image.0) / 2, rows.saturating_sub(image.1) / 2)
+}
+
+// moves an image left spaces to the right and top lines down. text modes pad every line. graphics protocols draw
+// the whole image at the cursor so only the start is padded. lines in header are above the image and always padded
+pub(crate) fn pad(header: &str, image: &[u8], (left, top): (u32, u32), text: bool, out: &mut impl Write)
+           -> std::io::Result<()> {
+    let padding = " ".repeat(left as usize);
+
+    out.write_all("\n".repeat(top as usize).as_bytes())?;
+
+    for line in header.split_inclusive('\n') {
+        out.write_all(padding.as_bytes())?;
+        out.write_all(line.as_bytes())?;
+    }
+
+    if !text {
+        out.write_all(padding.as_bytes())?;
+        return out.write_all(image);
+    }
+
+    for line in image.split_inclusive(|&b| b == b'\n') {
+        out.write_all(padding.as_bytes())?;
+        out.write_all(line)?;
+    }
+    Ok(())
+}
+
+// frames an image drawn by a text mode with box-drawing characters. color is the escape the border is drawn with
+pub(crate) fn border(image: &[u8], width: u32, color: Option<String>) -> Vec<u8> {
+    let (start, end) = match &color {
+        Some(color) => (color.as_str(), "\x1b[0m"),
+        None => ("", "")
+    };
+
+    let width = width as usize;
+    let mut out = format!("{}┌{}┐{}\n", start, "─".repeat(width), end);
+    for line in String::from_utf8_lossy(image).lines() {
+        // short lines are padded so the right side lines up
+        let padding = " ".repeat(width.saturating_sub(visible_width(line)));
+        out.push_str(&format!("{}│{}{}{}{}│{}\n", start, end, line, padding, start, end));
+    }
+    out.push_str(&format!("{}└{}┘{}\n", start, "─".repeat(width), end));
+    out.into_bytes()
+}
+
+// a line with text in the middle of width columns. text that's too wide is cut off with an ellipsis. reset clears
+// the colors first so the text isn't drawn with the image's colors
+pub(crate) fn title(text: &str, width: u32, reset: bool) -> String {
+    let width = width as usize;
+    let mut text = text.to_string();
+
+    if UnicodeWidthStr::width(text.as_str()) > width {
+        let mut truncated = String::new();
+        for c in text.chars() {
+            if UnicodeWidthStr::width(truncated.as_str()) + UnicodeWidthChar::width(c).unwrap_or(0) + 1 > width {
+                break;
+            }
+            truncated.push(c);
+        }
+        if width > 0 {
+            truncated.push('…');
+        }
+        text = truncated;
+    }
+
+    let padding = (width - UnicodeWidthStr::width(text.as_str())) / 2;
+    format!("{}{}{}\n", if reset { "\x1b[0m" } else { "" }, " ".repeat(padding), text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn titles() {
+        assert_eq!(title("cat", 9, true), "\x1b[0m   cat\n");
+        assert_eq!(title("cat", 9, false), "   cat\n");
+        assert_eq!(title("a long title", 5, false), "a lo…\n");
+    }
+
+    #[test]
+    fn centering() {
+        assert_eq!(center_padding((10, 5), (40, 12)), (15, 3));
+        // images bigger than the terminal stay at the edge
+        assert_eq!(center_padding((50, 20), (40, 12)), (0, 0));
+
+        let mut out = Vec::new();
+        pad("", b"ab\ncd\n", (2, 1), true, &mut out).unwrap();
+        assert_eq!(out, b"\n  ab\n  cd\n");
+    }
+
+    #[test]
+    fn montage_rows_line_up() {
+        let image = "\x1b[48;2;255;0;0m    \x1b[0m\n".repeat(4).into_bytes();
+        let sheet = String::from_utf8(montage(&[image.clone(), image.clone(), image], 3)).unwrap();
+
+        assert_eq!(sheet.lines().count(), 4);
+        for line in sheet.lines() {
+            assert_eq!(visible_width(line), 4 * 3 + 2 * SEPARATOR.len());
+        }
+
+        // shorter images are padded with blank lines
+        let short = b"ab\n".to_vec();
+        let sheet = String::from_utf8(montage(&[b"ab\ncd\n".to_vec(), short], 2)).unwrap();
+        assert_eq!(sheet, "ab ab\ncd   \n");
+    }
+
+    #[test]
+    fn borders() {
+        let image = "\x1b[48;2;255;0;0m  \x1b[0m\n".repeat(2).into_bytes();
+        let framed = String::from_utf8(border(&image, 2, Some("\x1b[38;2;0;0;255m".to_string()))).unwrap();
+
+        assert_eq!(framed.lines().count(), 4);
+        for line in framed.lines() {
+            assert_eq!(visible_width(line), 4);
+        }
+        assert!(framed.starts_with("\x1b[38;2;0;0;255m┌──┐\x1b[0m\n"));
+        assert_eq!(String::from_utf8(border(b"a\n", 2, None)).unwrap(), "┌──┐\n│a │\n└──┘\n");
+    }
+
+    #[test]
+    fn crlf_line_endings() {
+        let mut out = Crlf(Vec::new());
+        out.write_all(b"ab\ncd\n").unwrap();
+        out.write_all(b"\n").unwrap();
+        assert_eq!(out.0, b"ab\r\ncd\r\n\r\n");
+    }
+
+    #[test]
+    fn contact_sheet_grids() {
+        // five captioned thumbnails four to a row like --contact-sheet draws them
+        let img = crate::DynamicImage::new_rgb8(40, 40);
+        let tiles: Vec<Vec<u8>> = (0..5).map(|i| {
+            let options = crate::RenderOptions {
+                size: crate::Size::Cells(20, 10),
+                title: Some(format!("{}.png", i)),
+                ..crate::RenderOptions::default()
+            };
+            let mut out = Vec::new();
+            crate::render_image(&img, &options, &mut out).unwrap();
+            out
+        }).collect();
+        let tile = String::from_utf8(tiles[0].clone()).unwrap();
+        let (width, height) = (visible_width(tile.lines().last().unwrap()), tile.lines().count());
+        assert_eq!(width, 20);
+
+        let sheet = String::from_utf8(montage(&tiles, 4)).unwrap();
+        let lines: Vec<&str> = sheet.lines().collect();
+        // two rows of thumbnails with a blank line between
+        assert_eq!(lines.len(), height * 2 + 1);
+        assert_eq!(visible_width(lines[0]), width * 4 + 3 * SEPARATOR.len());
+        assert_eq!(visible_width(lines[height + 1]), width);
+    }
+
+    #[test]
+    fn no_trailing_newline() {
+        let mut out = NoTrailingNewline::new(Vec::new());
+        out.write_all(b"ab\n").unwrap();
+        // newlines held back are written once something follows them
+        out.write_all(b"cd\n").unwrap();
+        out.write_all(b"\x1b[0m\n").unwrap();
+        assert_eq!(out.out, b"ab\ncd\n\x1b[0m");
+        assert_eq!(out.out.last(), Some(&b'm'));
+    }
+}
@@ -0,0 +1,910 @@
+//! Renders images in the terminal with colored blocks, text, or graphics protocols.
+
+use std::borrow::Cow;
+use std::io::{BufWriter, IsTerminal, Write};
+
+use image::imageops::FilterType;
+use image::GenericImageView;
+
+pub use image::DynamicImage;
+
+mod adjust;
+mod animation;
+mod cache;
+mod cells;
+mod color;
+mod dither;
+mod documents;
+mod graphics;
+#[cfg(feature = "color-management")]
+mod icc;
+mod input;
+mod layout;
+mod mask;
+mod output;
+mod progress;
+mod simulate;
+mod terminal;
+mod tonemap;
+mod watermark;
+
+pub use adjust::{parse_crop, parse_sharpen, trim_bounds, ROTATIONS};
+pub use animation::play;
+pub use cache::Cache;
+pub use color::{detect_colors, get_color_distance, get_colors, parse_color, ColorDistance, Colors, COLORS, COLOR_DISTANCES};
+pub use dither::{get_dither, Dither, DITHERS, DITHER_MATRICES};
+pub use input::{image_info, load_frames, load_image, load_image_streamed, unconverted_profile, ImageInfo};
+pub use layout::{montage, Crlf, NoTrailingNewline};
+pub use mask::{get_mask, Mask, MASKS};
+pub use output::{save_frames, save_image};
+pub use simulate::{get_deficiency, Deficiency, DEFICIENCIES};
+pub use terminal::detect_font_ratio;
+pub use tonemap::{get_tonemap, Tonemap, TONEMAPS};
+pub use watermark::{get_corner, Corner, CORNERS};
+
+/// Names of the resizing filters accepted by `get_filter`
+pub static FILTERS: [&str; 5] = [
+    "nearest",
+    "triangle",
+    "catmullrom",
+    "gaussian",
+    "lanczos3"
+];
+
+pub fn get_filter(filter: &str) -> Option<FilterType> {
+    match filter {
+        "nearest" => Some(image::imageops::Nearest),
+        "triangle" => Some(image::imageops::Triangle),
+        "catmullrom" => Some(image::imageops::CatmullRom),
+        "gaussian" => Some(image::imageops::Gaussian),
+        "lanczos3" => Some(image::imageops::Lanczos3),
+        _ => None
+    }
+}
+
+/// Names of the modes accepted by `get_mode`
+pub static MODES: [&str; 12] = [
+    "block",
+    "halfblock",
+    "quadrant",
+    "ascii",
+    "shades",
+    "braille",
+    "sixel",
+    "kitty",
+    "iterm",
+    "html",
+    "svg",
+    "json"
+];
+
+/// How pixels are drawn
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Mode {
+    Block,
+    HalfBlock,
+    Quadrant,
+    Ascii,
+    Shades,
+    Braille,
+    Sixel,
+    Kitty,
+    Iterm,
+    Html,
+    Svg,
+    Json
+}
+
+impl Mode {
+    /// Pixels drawn in each cell as (columns, rows)
+    pub fn cell_size(self) -> (u32, u32) {
+        match self {
+            Mode::Block | Mode::Ascii | Mode::Shades | Mode::Html => (1, 1),
+            Mode::HalfBlock => (1, 2),
+            Mode::Quadrant => (2, 2),
+            Mode::Braille => (2, 4),
+            // graphics protocols draw real pixels so guess the size of a cell
+            Mode::Sixel | Mode::Kitty | Mode::Iterm => (8, 16),
+            // svg draws a square for every pixel and json lists them
+            Mode::Svg | Mode::Json => (1, 1)
+        }
+    }
+
+    /// Graphics protocols, svg, and json draw real pixels instead of characters
+    pub fn is_graphics(self) -> bool {
+        matches!(self, Mode::Sixel | Mode::Kitty | Mode::Iterm | Mode::Svg | Mode::Json)
+    }
+
+    /// Modes that only draw characters and color escapes so their output can be laid out like text
+    pub fn is_text(self) -> bool {
+        matches!(self, Mode::Block | Mode::HalfBlock | Mode::Quadrant | Mode::Ascii | Mode::Shades | Mode::Braille)
+    }
+
+    /// Modes that write a document instead of drawing on the terminal
+    pub fn is_document(self) -> bool {
+        matches!(self, Mode::Html | Mode::Svg | Mode::Json)
+    }
+
+    /// Modes that draw with color escapes
+    pub fn uses_color(self) -> bool {
+        matches!(self, Mode::Block | Mode::HalfBlock | Mode::Quadrant)
+    }
+
+    pub fn supports_transparency(self) -> bool {
+        matches!(self, Mode::Kitty | Mode::Iterm | Mode::Json)
+    }
+}
+
+pub fn get_mode(mode: &str) -> Option<Mode> {
+    match mode {
+        "block" => Some(Mode::Block),
+        "halfblock" => Some(Mode::HalfBlock),
+        "quadrant" => Some(Mode::Quadrant),
+        "ascii" => Some(Mode::Ascii),
+        "shades" => Some(Mode::Shades),
+        "braille" => Some(Mode::Braille),
+        "sixel" => Some(Mode::Sixel),
+        "kitty" => Some(Mode::Kitty),
+        "iterm" => Some(Mode::Iterm),
+        "html" => Some(Mode::Html),
+        "svg" => Some(Mode::Svg),
+        "json" => Some(Mode::Json),
+        _ => None
+    }
+}
+
+/// Characters used by ascii mode when no charset is given
+pub static DEFAULT_CHARSET: &str = " .:-=+*#%@";
+
+/// How big to draw an image
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Size {
+    /// Fit inside a box of (columns, rows)
+    Cells(u32, u32),
+    /// A percentage of the original size
+    Percent(u32),
+    /// One pixel per cell, stretched to make up for the shape of cells
+    Original
+}
+
+impl Size {
+    // original is the size of the image in cells when it is drawn at its original size
+    fn cells(self, original: (u32, u32)) -> Option<(u32, u32)> {
+        match self {
+            // a box with no room would resize the image to nothing
+            Size::Cells(columns, rows) => Some((columns.max(1), rows.max(1))),
+            Size::Percent(percent) => Some(((original.0.saturating_mul(percent) / 100).max(1),
+                                            (original.1.saturating_mul(percent) / 100).max(1))),
+            Size::Original => None
+        }
+    }
+}
+
+// sizes set by the shell like COLUMNS and LINES
+fn env_size(name: &str) -> Option<u32> {
+    std::env::var(name).ok()?.parse().ok().filter(|&size| size > 0)
+}
+
+// the cells term fills in a terminal of columns by rows, or from env when there is no terminal. leaves a line for the
+// prompt so the top of the image doesn't scroll away
+fn term_size(terminal: Option<(u32, u32)>, env: impl Fn(&str) -> Option<u32>) -> Size {
+    let (columns, rows) = match terminal {
+        Some(terminal) => terminal,
+        None => (env("COLUMNS").unwrap_or(80), env("LINES").unwrap_or(24))
+    };
+    Size::Cells(columns, rows.saturating_sub(1).max(1))
+}
+
+/// Parses WIDTHxHEIGHT, N%, term, or original. When there is no terminal, like when piping the output, term is
+/// COLUMNS by LINES from the environment or 80x24
+pub fn get_size(size: &str) -> Option<Size> {
+    match size {
+        "term" => {
+            let terminal = terminal_size::terminal_size().map(|sz| (u32::from((sz.0).0), u32::from((sz.1).0)));
+            Some(term_size(terminal, env_size))
+        }
+        "original" => Some(Size::Original),
+        sz if sz.ends_with('%') => sz.trim_end_matches('%').parse().ok().map(Size::Percent),
+        sz => {
+            let captures = regex::Regex::new("^(\\d+)[Xx](\\d+)$").unwrap().captures(sz)?;
+            Some(Size::Cells(captures[1].parse().ok()?, captures[2].parse().ok()?))
+        }
+    }
+}
+
+/// Names of the resize modes accepted by `get_resize_mode`
+pub static RESIZE_MODES: [&str; 3] = [
+    "fit",
+    "fill",
+    "stretch"
+];
+
+/// How an image is resized to the box of cells it's drawn in
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ResizeMode {
+    /// As large as possible inside the box without changing its shape
+    Fit,
+    /// Cover the whole box without changing its shape and crop off what doesn't fit
+    Fill,
+    /// Exactly the size of the box
+    Stretch
+}
+
+pub fn get_resize_mode(mode: &str) -> Option<ResizeMode> {
+    match mode {
+        "fit" => Some(ResizeMode::Fit),
+        "fill" => Some(ResizeMode::Fill),
+        "stretch" => Some(ResizeMode::Stretch),
+        _ => None
+    }
+}
+
+// crops the middle of an image to the shape of a box of cells on screen so it fills the box when it's fit inside
+fn crop_to_fill(img: &DynamicImage, cells: (u32, u32), ratio: f32) -> DynamicImage {
+    let (width, height) = img.dimensions();
+    let aspect = cells.0 as f32 / (cells.1 as f32 * ratio);
+
+    if width as f32 / height as f32 > aspect {
+        let cropped = ((height as f32 * aspect).round() as u32).clamp(1, width);
+        img.crop_imm((width - cropped) / 2, 0, cropped, height)
+    } else {
+        let cropped = ((width as f32 / aspect).round() as u32).clamp(1, height);
+        img.crop_imm(0, (height - cropped) / 2, width, cropped)
+    }
+}
+
+/// Finds the largest size in pixels that fits in a box of cells without changing the shape of the image
+/// on screen. ratio is the height of a cell divided by its width
+pub fn fit_size(dimensions: (u32, u32), cells: (u32, u32), cell_size: (u32, u32), ratio: f32) -> (u32, u32) {
+    let aspect = dimensions.0 as f32 / dimensions.1 as f32;
+
+    // size of the box on screen measured in cell widths
+    let box_width = cells.0 as f32;
+    let box_height = cells.1 as f32 * ratio;
+
+    let (width, height) = if box_width / box_height > aspect {
+        (box_height * aspect, box_height)
+    } else {
+        (box_width, box_width / aspect)
+    };
+
+    let pixels = ((width * cell_size.0 as f32).round() as u32, (height / ratio * cell_size.1 as f32).round() as u32);
+    (pixels.0.max(1).min(cells.0 * cell_size.0), pixels.1.max(1).min(cells.1 * cell_size.1))
+}
+
+/// Settings for `render_image`
+#[derive(Clone, Debug)]
+pub struct RenderOptions {
+    pub mode: Mode,
+    pub colors: Colors,
+    /// How the closest color in the terminal's palette is picked
+    pub color_distance: ColorDistance,
+    /// How colors are reduced when the terminal doesn't support true color
+    pub dither: Dither,
+    /// Width and height of the Bayer matrix used by ordered dithering. Must be 2, 4, or 8
+    pub dither_matrix: u32,
+    /// Seed for the noise of random dithering, which uses 0 without one, and of seeded Floyd-Steinberg dithering. The
+    /// same seed gives the same output
+    pub dither_seed: Option<u64>,
+    pub size: Size,
+    /// Most columns the image can take up
+    pub max_width: Option<u32>,
+    /// Most rows the image can take up
+    pub max_height: Option<u32>,
+    /// How the image is resized to the box it's drawn in
+    pub resize_mode: ResizeMode,
+    /// Repeat the image across the whole box like wallpaper instead of resizing it
+    pub tile: bool,
+    /// Filter used to resize the image
+    pub filter: FilterType,
+    /// Resize in linear light instead of sRGB so shrunk images keep their brightness. Slower
+    pub linear_resize: bool,
+    /// Height of a terminal cell divided by its width
+    pub font_ratio: f32,
+    /// Characters used by ascii mode ordered from darkest to lightest
+    pub charset: Vec<char>,
+    /// Minimum brightness of a dot in braille mode
+    pub threshold: u8,
+    /// Drawn in each cell of block mode
+    pub cell: String,
+    /// Drawn behind transparent pixels in modes that can't draw transparency
+    pub background: [u8; 3],
+    /// Draw a checkerboard with squares this many pixels wide behind transparent pixels instead of background
+    pub checker: Option<u32>,
+    /// Only clear colors at the end of each line in halfblock mode instead of after every cell. The output is much
+    /// smaller but a cut off line can leave the terminal colored
+    pub no_reset: bool,
+    /// Pad the image so it's in the middle of the terminal on stdout. Ignored when there is no terminal. Only meant for
+    /// when out is that terminal
+    pub center: bool,
+    /// Spaces before every line of the image, added to the padding from center. Ignored by html, svg, and json modes
+    pub indent: u32,
+    /// Show how many lines of text modes have been drawn on stderr when it's a terminal
+    pub progress: bool,
+    /// Frame the image with box-drawing characters. Only text modes draw a border
+    pub border: bool,
+    /// Color of the border. The terminal's text color is used when this is None
+    pub border_color: Option<[u8; 3]>,
+    /// Image the same size as the one drawn to compare it with. Pixels that changed are drawn red, brighter the
+    /// more they changed, and the rest are dimmed
+    pub diff: Option<DynamicImage>,
+    /// Image drawn over the input, stretched to the same size, before anything else
+    pub overlay: Option<DynamicImage>,
+    /// Make everything outside this shape transparent so --background or bg_image shows through
+    pub mask: Option<Mask>,
+    /// Pixels the corners of a rounded mask are rounded by. An eighth of the shorter side when this is None
+    pub radius: Option<u32>,
+    /// Image drawn behind the input, stretched to the size it's drawn at, so it shows through transparent pixels
+    pub bg_image: Option<DynamicImage>,
+    /// Text drawn over a corner of the image after resizing
+    pub watermark: Option<String>,
+    /// Corner the watermark is drawn in
+    pub watermark_corner: Corner,
+    /// Color of the watermark
+    pub watermark_color: [u8; 3],
+    /// How opaque the overlay is from 0 to 1
+    pub opacity: f32,
+    /// Text drawn in the middle of a line above the image. Ignored by html, svg, and json modes
+    pub title: Option<String>,
+    /// Width and height of each pixel in svg mode
+    pub svg_pixel_size: u32,
+    /// How 16 bit images are brought down to 8 bits before anything else. They're scaled evenly when this is None
+    pub tonemap: Option<Tonemap>,
+    /// Show the image as someone with this color vision deficiency sees it
+    pub simulate: Option<Deficiency>,
+    /// Remove the fully transparent rows and columns around the edges of the image before anything else
+    pub trim: bool,
+    /// Trim rows and columns of this color instead of transparent ones
+    pub trim_color: Option<[u8; 3]>,
+    /// Part of the image to draw as (x, y, width, height) in pixels. Measured after trimming
+    pub crop: Option<(u32, u32, u32, u32)>,
+    /// Mirror the image left to right
+    pub flip_h: bool,
+    /// Mirror the image top to bottom
+    pub flip_v: bool,
+    /// Degrees to rotate the image clockwise by after flipping it. Must be 0, 90, 180, or 270
+    pub rotate: u32,
+    /// Replace colors with their luminance
+    pub grayscale: bool,
+    /// Added to every channel. From -255 to 255
+    pub brightness: i32,
+    /// Percent to increase contrast by. From -100, which makes the image solid gray, to 100
+    pub contrast: f32,
+    /// Degrees to rotate the hue of every color by
+    pub hue: i32,
+    /// Replace each color channel c with 255 - c
+    pub invert: bool,
+    /// Gaussian blur applied before resizing with this standard deviation
+    pub blur: Option<f32>,
+    /// Tint the resized image with a sepia tone
+    pub sepia: bool,
+    /// Gamma correction applied after resizing. 1.0 changes nothing and higher values brighten midtones
+    pub gamma: f32,
+    /// Draw the outlines in the image instead of its colors after resizing
+    pub edges: bool,
+    /// Make pixels at least this bright white and the rest black after resizing
+    pub binarize: Option<u8>,
+    /// Unsharp mask applied after resizing as (amount, threshold)
+    pub sharpen: Option<(f32, i32)>,
+    /// Reduce the image to a palette of this many colors picked from it after resizing
+    pub quantize: Option<u32>
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            mode: Mode::Block,
+            colors: Colors::TrueColor,
+            color_distance: ColorDistance::Rgb,
+            dither: Dither::None,
+            dither_matrix: 4,
+            dither_seed: None,
+            size: Size::Original,
+            max_width: None,
+            max_height: None,
+            resize_mode: ResizeMode::Fit,
+            tile: false,
+            filter: FilterType::Nearest,
+            linear_resize: false,
+            font_ratio: 2.0,
+            charset: DEFAULT_CHARSET.chars().collect(),
+            threshold: 128,
+            cell: " ".to_string(),
+            background: [0, 0, 0],
+            checker: None,
+            no_reset: false,
+            center: false,
+            indent: 0,
+            progress: false,
+            border: false,
+            border_color: None,
+            title: None,
+            diff: None,
+            overlay: None,
+            mask: None,
+            radius: None,
+            bg_image: None,
+            watermark: None,
+            watermark_corner: Corner::BottomRight,
+            watermark_color: [255, 255, 255],
+            opacity: 1.0,
+            svg_pixel_size: 10,
+            tonemap: None,
+            simulate: None,
+            trim: false,
+            trim_color: None,
+            crop: None,
+            flip_h: false,
+            flip_v: false,
+            rotate: 0,
+            grayscale: false,
+            brightness: 0,
+            contrast: 0.0,
+            hue: 0,
+            invert: false,
+            blur: None,
+            sepia: false,
+            gamma: 1.0,
+            edges: false,
+            binarize: None,
+            sharpen: None,
+            quantize: None
+        }
+    }
+}
+
+struct Prepared<'a> {
+    img: Cow<'a, DynamicImage>,
+    // size of the image on screen in pixels
+    target: (u32, u32),
+    // box of cells the image was fit inside
+    cells: Option<(u32, u32)>
+}
+
+// crops, adjusts, and resizes an image to draw in cells of cell_size pixels that are ratio times taller than they
+// are wide
+fn prepare<'a>(img: &'a DynamicImage, options: &RenderOptions, cell_size: (u32, u32), ratio: f32, resize: bool)
+               -> Result<Prepared<'a>, String> {
+    let mut prepared = crop_and_resize(img, options, cell_size, ratio, resize)?;
+
+    // the other image goes through the same steps so the same pixels are compared
+    if let Some(other) = &options.diff {
+        if other.dimensions() != img.dimensions() {
+            return Err(format!("The image to compare is {}x{} but this one is {}x{}",
+                               other.width(), other.height(), img.width(), img.height()));
+        }
+        let other = crop_and_resize(other, options, cell_size, ratio, resize)?;
+        prepared.img = Cow::Owned(adjust::diff(&prepared.img, &other.img));
+    }
+
+    if let Some(backdrop) = &options.bg_image {
+        prepared.img = Cow::Owned(adjust::underlay(&prepared.img, backdrop, options.filter));
+    }
+
+    if let Some(adjusted) = adjust::after_resize(&prepared.img, options) {
+        prepared.img = Cow::Owned(adjusted);
+    }
+
+    if let Some(text) = &options.watermark {
+        let (corner, color) = (options.watermark_corner, options.watermark_color);
+        prepared.img = Cow::Owned(watermark::watermark(&prepared.img, text, corner, color));
+    }
+
+    Ok(prepared)
+}
+
+fn crop_and_resize<'a>(img: &'a DynamicImage, options: &RenderOptions, cell_size: (u32, u32), ratio: f32, resize: bool)
+                       -> Result<Prepared<'a>, String> {
+    let mut img = Cow::Borrowed(img);
+
+    if let Some(tonemapped) = options.tonemap.and_then(|tonemap| tonemap::tonemap(&img, tonemap)) {
+        img = Cow::Owned(tonemapped);
+    }
+
+    if let Some(deficiency) = options.simulate {
+        img = Cow::Owned(simulate::simulate(&img, deficiency));
+    }
+
+    // images that would be trimmed away completely are left alone
+    if options.trim {
+        if let Some((x, y, width, height)) = trim_bounds(&img, options.trim_color) {
+            img = Cow::Owned(img.crop_imm(x, y, width, height));
+        }
+    }
+
+    if let Some(top) = &options.overlay {
+        img = Cow::Owned(adjust::overlay(&img, top, options.opacity, options.filter));
+    }
+
+    if let Some(rect) = options.crop {
+        img = Cow::Owned(adjust::crop(&img, rect)?);
+    }
+
+    if let Some(adjusted) = adjust::before_resize(&img, options) {
+        img = Cow::Owned(adjusted);
+    }
+
+    // masking before resizing keeps circles round when cells aren't square
+    if let Some(shape) = options.mask {
+        img = Cow::Owned(mask::mask(&img, shape, options.radius));
+    }
+
+    // cells are taller than they are wide so stretch the image to keep pixels square
+    let dimensions = img.dimensions();
+    let original = ((dimensions.0 as f32 * ratio * cell_size.0 as f32 / cell_size.1 as f32).round() as u32,
+                    dimensions.1);
+
+    let original_cells = (original.0 / cell_size.0, original.1 / cell_size.1);
+    let cells = options.size.cells(original_cells);
+
+    // the maximums shrink the box the image is fit inside so it keeps its shape
+    let cells = if options.max_width.is_some() || options.max_height.is_some() {
+        let (width, height) = cells.unwrap_or(original_cells);
+        Some((options.max_width.map_or(width, |max| width.min(max)), options.max_height.map_or(height, |max| height.min(max))))
+    } else {
+        cells
+    };
+
+    // tiles repeat the image across the whole box without resizing it
+    if let (Some(cells), true) = (cells, options.tile) {
+        let target = (cells.0 * cell_size.0, cells.1 * cell_size.1);
+        let tile = img.to_rgba8();
+        img = Cow::Owned(DynamicImage::ImageRgba8(image::RgbaImage::from_fn(target.0, target.1, |x, y| {
+            *tile.get_pixel(x % tile.width(), y % tile.height())
+        })));
+        return Ok(Prepared { img, target, cells: Some(cells) });
+    }
+
+    let target = match (cells, options.resize_mode) {
+        (Some(cells), ResizeMode::Fit) => fit_size(dimensions, cells, cell_size, ratio),
+        (Some(cells), ResizeMode::Fill) => {
+            img = Cow::Owned(crop_to_fill(&img, cells, ratio));
+            fit_size(img.dimensions(), cells, cell_size, ratio)
+        }
+        (Some(cells), ResizeMode::Stretch) => (cells.0 * cell_size.0, cells.1 * cell_size.1),
+        (None, _) => original
+    };
+
+    if resize && target != img.dimensions() {
+        img = Cow::Owned(if options.linear_resize {
+            adjust::resize_linear(&img, target.0, target.1, options.filter)
+        } else {
+            img.resize_exact(target.0, target.1, options.filter)
+        });
+    }
+
+    Ok(Prepared { img, target, cells })
+}
+
+/// Largest size in pixels an image is resized to when it's drawn with options, or None when that depends on the
+/// image. Used to shrink images while they're decoded with `load_image_streamed`
+pub fn largest_size(options: &RenderOptions) -> Option<(u32, u32)> {
+    // tiles are drawn at the image's own size
+    let cells = match options.size {
+        Size::Cells(columns, rows) if options.crop.is_none() && !options.tile => (columns, rows),
+        _ => return None
+    };
+    let cells = (cells.0.min(options.max_width.unwrap_or(u32::MAX)), cells.1.min(options.max_height.unwrap_or(u32::MAX)));
+
+    let cell_size = options.mode.cell_size();
+    Some((cells.0.saturating_mul(cell_size.0), cells.1.saturating_mul(cell_size.1)))
+}
+
+/// Crops, adjusts, and resizes an image like `render_image` does but with square pixels so sizes are in pixels
+/// instead of cells
+pub fn resize_image(img: &DynamicImage, options: &RenderOptions) -> Result<DynamicImage, String> {
+    prepare(img, options, (1, 1), 1.0, true).map(|prepared| prepared.img.into_owned())
+}
+
+// images are shrunk to fit in this many pixels on each side before their palette is found
+static PALETTE_SIZE: u32 = 100;
+
+/// Writes the count most common colors in an image after it's adjusted as a swatch and hex code on each line, most
+/// common first
+pub fn render_palette(img: &DynamicImage, count: u32, options: &RenderOptions, out: &mut impl Write)
+                      -> Result<(), String> {
+    // finding colors in a small copy is much faster. resizing without blending keeps the colors that are there
+    let (width, height) = img.dimensions();
+    let options = RenderOptions {
+        size: if width > PALETTE_SIZE || height > PALETTE_SIZE {
+            Size::Cells(PALETTE_SIZE, PALETTE_SIZE)
+        } else {
+            Size::Original
+        },
+        resize_mode: ResizeMode::Fit,
+        tile: false,
+        filter: FilterType::Nearest,
+        ..options.clone()
+    };
+    let img = resize_image(img, &options)?;
+    let palette = adjust::palette(&img, count, options.color_distance);
+    let total: usize = palette.iter().map(|&(_, pixels)| pixels).sum();
+
+    let mut out = BufWriter::new(out);
+    for (color, pixels) in palette {
+        let hex = format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2]);
+        let share = 100.0 * pixels as f32 / total as f32;
+        let written = if options.mode.uses_color() {
+            let swatch = color::color_escape(color, options.colors, options.color_distance, true);
+            writeln!(out, "{}      \x1b[0m {} {:5.1}%", swatch, hex, share)
+        } else {
+            writeln!(out, "{} {:5.1}%", hex, share)
+        };
+        written.map_err(|e| e.to_string())?;
+    }
+    out.flush().map_err(|e| e.to_string())
+}
+
+/// An image cropped, adjusted, and resized by `prepare_image` so it can be drawn later
+#[derive(Clone, Debug)]
+pub struct PreparedImage {
+    pub img: DynamicImage,
+    /// Size of the image on screen in pixels
+    pub target: (u32, u32),
+    /// Box of cells the image was fit inside
+    pub cells: Option<(u32, u32)>
+}
+
+// pixels in each cell and how many times taller than wide cells are
+fn cell_shape(options: &RenderOptions) -> ((u32, u32), f32) {
+    let cell_size = options.mode.cell_size();
+
+    // pixels from graphics protocols are square so only the guessed cell size matters
+    let ratio = if options.mode.is_graphics() {
+        cell_size.1 as f32 / cell_size.0 as f32
+    } else {
+        options.font_ratio
+    };
+    (cell_size, ratio)
+}
+
+/// Crops, adjusts, and resizes an image like `render_image` does without drawing it
+pub fn prepare_image(img: &DynamicImage, options: &RenderOptions) -> Result<PreparedImage, String> {
+    let (cell_size, ratio) = cell_shape(options);
+    let Prepared { img, target, cells } = prepare(img, options, cell_size, ratio, options.mode != Mode::Iterm)?;
+    Ok(PreparedImage { img: img.into_owned(), target, cells })
+}
+
+/// Draws an image from `prepare_image` with the options it was prepared with. Returns the size in pixels it was
+/// resized to
+pub fn render_prepared(prepared: &PreparedImage, options: &RenderOptions, out: &mut impl Write)
+                       -> Result<(u32, u32), String> {
+    let PreparedImage { img, target, cells } = prepared;
+    render_resized(Prepared { img: Cow::Borrowed(img), target: *target, cells: *cells }, options, out)
+}
+
+/// Resizes an image and writes it to out. Returns the size in pixels it was resized to
+pub fn render_image(img: &DynamicImage, options: &RenderOptions, out: &mut impl Write) -> Result<(u32, u32), String> {
+    let (cell_size, ratio) = cell_shape(options);
+
+    // iTerm scales the image to the requested size itself
+    let prepared = prepare(img, options, cell_size, ratio, options.mode != Mode::Iterm)?;
+    render_resized(prepared, options, out)
+}
+
+// everything render_image does after resizing
+fn render_resized(prepared: Prepared, options: &RenderOptions, out: &mut impl Write) -> Result<(u32, u32), String> {
+    let Prepared { mut img, target, cells } = prepared;
+    let cell_size = options.mode.cell_size();
+
+    if !options.mode.supports_transparency() {
+        img = Cow::Owned(color::composite(&img, options.background, options.checker));
+    }
+
+    if options.mode.uses_color() && options.colors != Colors::TrueColor && options.dither != Dither::None {
+        let (dither, matrix, seed) = (options.dither, options.dither_matrix, options.dither_seed);
+        img = Cow::Owned(dither::dither(&img, options.colors, options.color_distance, dither, matrix, seed));
+    }
+
+    // most writes are tiny so buffer them instead of making a syscall for each one
+    let mut out = BufWriter::new(out);
+
+    let terminal = match terminal_size::terminal_size() {
+        Some(sz) if options.center && !options.mode.is_document() => Some((u32::from((sz.0).0), u32::from((sz.1).0))),
+        _ => None
+    };
+
+    // size of the image on screen. iTerm fits the image in the same cells as the other graphics protocols
+    let mut size = (target.0.div_ceil(cell_size.0), target.1.div_ceil(cell_size.1));
+
+    let border = options.border && options.mode.is_text();
+    let title = options.title.as_ref().filter(|_| !options.mode.is_document());
+    let indent = if options.mode.is_document() { 0 } else { options.indent };
+    if terminal.is_none() && !border && title.is_none() && indent == 0 {
+        draw_lines(&img, options, cells, size.1, &mut out)?;
+        out.flush().map_err(|e| e.to_string())?;
+        return Ok(target);
+    }
+
+    let mut buffer = Vec::new();
+    draw_lines(&img, options, cells, size.1, &mut buffer)?;
+
+    if border {
+        let color = options.border_color.map(|color| color::color_escape(color, options.colors, options.color_distance, false));
+        buffer = layout::border(&buffer, size.0, color);
+        size = (size.0 + 2, size.1 + 2);
+    }
+
+    let title = match title {
+        Some(title) => {
+            size.1 += 1;
+            layout::title(title, size.0, options.mode.uses_color())
+        }
+        None => String::new()
+    };
+
+    let (left, top) = terminal.map_or((0, 0), |terminal| layout::center_padding(size, terminal));
+    layout::pad(&title, &buffer, (left + indent, top), options.mode.is_text(), &mut out)
+        .and_then(|_| out.flush())
+        .map_err(|e| e.to_string())?;
+    Ok(target)
+}
+
+// draws the image with a progress bar on stderr out of rows lines when it's asked for. only text modes draw a line
+// at a time, and the bar follows the lines as they're drawn even when they're buffered for a border or title
+fn draw_lines(img: &DynamicImage, options: &RenderOptions, cells: Option<(u32, u32)>, rows: u32, out: &mut impl Write)
+    -> Result<(), String> {
+    if !options.progress || !options.mode.is_text() || !std::io::stderr().is_terminal() {
+        return draw(img, options, cells, None, out);
+    }
+
+    let progress = progress::Progress::new(rows);
+    draw(img, options, cells, Some(&progress), out)?;
+    progress.finish();
+    Ok(())
+}
+
+// cells is the box iTerm fits the image inside. progress counts the lines of text modes as they're drawn
+fn draw(img: &DynamicImage, options: &RenderOptions, cells: Option<(u32, u32)>, progress: Option<&progress::Progress>,
+        out: &mut impl Write) -> Result<(), String> {
+    let written = match options.mode {
+        Mode::Block => cells::write_blocks(img, options.colors, options.color_distance, &options.cell, progress, out),
+        Mode::HalfBlock => {
+            cells::write_halfblocks(img, options.colors, options.color_distance, !options.no_reset, progress, out)
+        },
+        Mode::Quadrant => cells::write_quadrants(img, options.colors, options.color_distance, progress, out),
+        Mode::Ascii => cells::write_ascii(img, &options.charset, progress, out),
+        Mode::Shades => cells::write_ascii(img, &cells::SHADES, progress, out),
+        Mode::Braille => cells::write_braille(img, options.threshold, progress, out),
+        Mode::Sixel => return graphics::write_sixel(img, out),
+        Mode::Kitty => graphics::write_kitty(img, out),
+        Mode::Iterm => return graphics::write_iterm(img, cells, options.resize_mode == ResizeMode::Stretch, out),
+        Mode::Html => documents::write_html(img, out),
+        Mode::Svg => documents::write_svg(img, options.svg_pixel_size, out),
+        Mode::Json => documents::write_json(img, out)
+    };
+    written.map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fit_size_keeps_shape() {
+        // an 80x23 box is 80 cells wide and 46 cell widths tall at a font ratio of 2
+        assert_eq!(fit_size((200, 100), (80, 23), (1, 1), 2.0), (80, 20));
+        assert_eq!(fit_size((100, 200), (80, 23), (1, 1), 2.0), (23, 23));
+        assert_eq!(fit_size((100, 100), (80, 23), (1, 1), 2.0), (46, 23));
+        assert_eq!(fit_size((200, 100), (80, 23), (1, 2), 2.0), (80, 40));
+    }
+
+    #[test]
+    fn percent_of_original() {
+        assert_eq!(Size::Percent(50).cells((200, 100)), Some((100, 50)));
+        assert_eq!(Size::Percent(200).cells((200, 100)), Some((400, 200)));
+        // too small to see still draws a cell
+        assert_eq!(Size::Percent(1).cells((20, 10)), Some((1, 1)));
+    }
+
+    #[test]
+    fn term_without_a_terminal() {
+        assert_eq!(term_size(Some((120, 40)), |_| Some(10)), Size::Cells(120, 39));
+        assert_eq!(term_size(None, |_| None), Size::Cells(80, 23));
+
+        let env = |name: &str| if name == "COLUMNS" { Some(100) } else { None };
+        assert_eq!(term_size(None, env), Size::Cells(100, 23));
+        assert_eq!(term_size(None, |_| Some(1)), Size::Cells(1, 1));
+    }
+
+    #[test]
+    fn largest_size_of_tiles() {
+        let options = RenderOptions { size: Size::Cells(80, 23), ..RenderOptions::default() };
+        assert_eq!(largest_size(&options), Some((80, 23)));
+        assert_eq!(largest_size(&RenderOptions { tile: true, ..options }), None);
+    }
+
+    #[test]
+    fn max_width_caps_rows() {
+        // like --cols 40 in a 120 column terminal
+        let options = RenderOptions { size: Size::Cells(120, 40), max_width: Some(40), ..RenderOptions::default() };
+        let img = DynamicImage::ImageRgb8(image::RgbImage::from_pixel(300, 50, image::Rgb([255, 0, 0])));
+        let mut out = Vec::new();
+        render_image(&img, &options, &mut out).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.lines().count() > 0);
+        // block mode draws a space in each cell
+        for line in out.lines() {
+            assert_eq!(line.matches(' ').count(), 40);
+        }
+    }
+
+    #[test]
+    fn max_width_keeps_shape() {
+        let img = DynamicImage::new_rgb8(200, 100);
+        let options = RenderOptions { size: Size::Original, max_width: Some(50), ..RenderOptions::default() };
+        assert_eq!(resize_image(&img, &options).unwrap().dimensions(), (50, 25));
+
+        // the smaller of the limits wins
+        let options = RenderOptions { max_height: Some(10), ..options };
+        assert_eq!(resize_image(&img, &options).unwrap().dimensions(), (20, 10));
+    }
+
+    #[test]
+    fn resize_modes() {
+        // red counts up from the left edge
+        let img = DynamicImage::ImageRgb8(image::RgbImage::from_fn(200, 100, |x, _| image::Rgb([x as u8, 0, 0])));
+        let resize = |resize_mode| {
+            let options = RenderOptions { size: Size::Cells(50, 50), resize_mode, ..RenderOptions::default() };
+            resize_image(&img, &options).unwrap().to_rgb8()
+        };
+
+        let fit = resize(ResizeMode::Fit);
+        assert_eq!(fit.dimensions(), (50, 25));
+        assert!(fit.get_pixel(0, 0)[0] < 5);
+
+        // fill covers the box and crops off the left and right quarters
+        let fill = resize(ResizeMode::Fill);
+        assert_eq!(fill.dimensions(), (50, 50));
+        assert!((45..55).contains(&fill.get_pixel(0, 0)[0]), "{:?}", fill.get_pixel(0, 0));
+
+        let stretch = resize(ResizeMode::Stretch);
+        assert_eq!(stretch.dimensions(), (50, 50));
+        assert!(stretch.get_pixel(0, 0)[0] < 5);
+        assert!(stretch.get_pixel(49, 49)[0] > 190);
+    }
+
+    #[test]
+    fn tiles_wrap() {
+        let img = DynamicImage::ImageRgb8(image::RgbImage::from_fn(2, 2, |x, y| image::Rgb([x as u8, y as u8, 0])));
+        let options = RenderOptions { size: Size::Cells(6, 4), tile: true, ..RenderOptions::default() };
+        let tiled = resize_image(&img, &options).unwrap().to_rgb8();
+
+        assert_eq!(tiled.dimensions(), (6, 4));
+        for (x, y, pixel) in tiled.enumerate_pixels() {
+            assert_eq!(pixel.0, [(x % 2) as u8, (y % 2) as u8, 0]);
+        }
+    }
+
+    #[test]
+    fn palettes_of_two_colors() {
+        // three quarters red and one quarter blue
+        let img = DynamicImage::ImageRgb8(image::RgbImage::from_fn(8, 8, |x, _| {
+            image::Rgb(if x < 6 { [255, 0, 0] } else { [0, 0, 255] })
+        }));
+        let options = RenderOptions { mode: Mode::Ascii, ..RenderOptions::default() };
+        let mut out = Vec::new();
+        render_palette(&img, 2, &options, &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "#ff0000  75.0%\n#0000ff  25.0%\n");
+    }
+
+    #[test]
+    fn indents() {
+        let img = DynamicImage::ImageRgb8(image::RgbImage::from_pixel(8, 8, image::Rgb([255, 255, 255])));
+        let render = |indent| {
+            let options = RenderOptions { size: Size::Cells(4, 2), resize_mode: ResizeMode::Stretch, mode: Mode::Ascii,
+                                          indent, ..RenderOptions::default() };
+            let mut out = Vec::new();
+            render_image(&img, &options, &mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        let flush = render(0);
+        let indented = render(4);
+        assert_eq!(indented.lines().count(), 2);
+        for (line, indented) in flush.lines().zip(indented.lines()) {
+            assert_eq!(indented, format!("    {}", line));
+        }
+    }
+}
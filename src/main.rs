@@ -1,96 +1,1124 @@
-#![feature(with_options)]
+use std::ffi::OsString;
+use std::io::{stdout, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
-use std::io::{stdout, Write};
+use clap::{Arg, ArgMatches, SubCommand};
+use image::{ColorType, GenericImageView};
+use image_render::{
+    detect_colors, detect_font_ratio, get_color_distance, get_colors, get_corner, get_deficiency, get_dither,
+    get_filter, get_mask, get_mode, get_resize_mode, get_size, get_tonemap, image_info, largest_size, load_frames,
+    load_image, load_image_streamed, montage, parse_color, parse_crop, parse_sharpen, play, prepare_image,
+    render_palette, render_prepared, resize_image, save_frames, save_image, trim_bounds, unconverted_profile, Cache, Crlf, Dither,
+    ImageInfo, Mode, NoTrailingNewline, RenderOptions, Size, COLORS, COLOR_DISTANCES, CORNERS, DEFAULT_CHARSET,
+    DEFICIENCIES, DITHERS, DITHER_MATRICES, FILTERS, MASKS, MODES, RESIZE_MODES, ROTATIONS, TONEMAPS
+};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
-use clap::Arg;
-use image::GenericImageView;
+// clap exits with 1 for bad arguments. rendering errors also use 1
+static EXIT_ERROR: i32 = 1;
+// the input couldn't be read or decoded
+static EXIT_INPUT: i32 = 2;
+// the output couldn't be opened or written
+static EXIT_OUTPUT: i32 = 3;
 
-macro_rules! err_and_return {
-    ($e: expr) => {{
-        eprintln!("{}", $e);
-        return;
+// warned about by --color-managed for images that are drawn without being converted
+static UNCONVERTED_PROFILE: &str = "the embedded ICC profile can't be converted so the image is drawn as sRGB";
+
+// how much is written to stderr
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Verbosity {
+    // errors aren't printed, only the exit status tells what went wrong
+    Quiet,
+    Normal,
+    // sizes and timings are printed for each input
+    Verbose
+}
+
+// prints to stderr unless --quiet was passed
+macro_rules! report {
+    ($verbosity: expr, $($arg: tt)*) => {
+        if $verbosity != Verbosity::Quiet {
+            eprintln!($($arg)*);
+        }
+    }
+}
+
+macro_rules! err_and_exit {
+    ($verbosity: expr, $code: expr, $e: expr) => {{
+        report!($verbosity, "{}", $e);
+        std::process::exit($code);
     }}
 }
 
-static FILTERS: [&str; 5] = [
-    "nearest",
-    "triangle",
-    "catmullrom",
-    "gaussian",
-    "lanczos3"
-];
+fn validate_charset(charset: String) -> Result<(), String> {
+    if charset.chars().count() < 2 {
+        return Err("Charset must contain at least two characters".to_string());
+    }
+    // wide and zero width characters would break up the columns of the image
+    if let Some(c) = charset.chars().find(|&c| UnicodeWidthChar::width(c) != Some(1)) {
+        return Err(format!("Charset characters must be exactly one column wide but {:?} isn't", c));
+    }
+    Ok(())
+}
+
+fn validate_cell(cell: String) -> Result<(), String> {
+    if UnicodeWidthStr::width(cell.as_str()) != 1 {
+        return Err("Character must be exactly one column wide".to_string());
+    }
+    Ok(())
+}
+
+fn validate_threshold(threshold: String) -> Result<(), String> {
+    match threshold.parse::<u8>() {
+        Ok(_) => Ok(()),
+        Err(_) => Err("Threshold must be a number from 0 to 255".to_string())
+    }
+}
+
+fn validate_binarize(level: String) -> Result<(), String> {
+    match level.parse::<u8>() {
+        Ok(_) => Ok(()),
+        Err(_) => Err("Binarize level must be a number from 0 to 255".to_string())
+    }
+}
 
-fn get_filter(filter: &str) -> Option<image::imageops::FilterType> {
-    match filter {
-        "nearest" => Some(image::imageops::Nearest),
-        "triangle" => Some(image::imageops::Triangle),
-        "catmullrom" => Some(image::imageops::CatmullRom),
-        "gaussian" => Some(image::imageops::Gaussian),
-        "lanczos3" => Some(image::imageops::Lanczos3),
-        _ => None
+fn validate_font_ratio(ratio: String) -> Result<(), String> {
+    match ratio.parse::<f32>() {
+        Ok(ratio) if ratio > 0.0 && ratio.is_finite() => Ok(()),
+        _ => Err("Font ratio must be a positive number".to_string())
+    }
+}
+
+fn validate_color(color: String) -> Result<(), String> {
+    match parse_color(&color) {
+        Some(_) => Ok(()),
+        None => Err("Color must be in the format RRGGBB".to_string())
+    }
+}
+
+fn validate_checker_size(size: String) -> Result<(), String> {
+    match size.parse::<u32>() {
+        Ok(size) if size > 0 => Ok(()),
+        _ => Err("Checker size must be a whole number above 0".to_string())
+    }
+}
+
+fn validate_crop(crop: String) -> Result<(), String> {
+    match parse_crop(&crop) {
+        Some(_) => Ok(()),
+        None => Err("Crop must be in the format X,Y,WIDTH,HEIGHT with a width and height above 0".to_string())
+    }
+}
+
+fn validate_sharpen(sharpen: String) -> Result<(), String> {
+    match parse_sharpen(&sharpen) {
+        Some(_) => Ok(()),
+        None => Err("Sharpen must be in the format AMOUNT,THRESHOLD with a positive amount and a threshold from 0 to 255"
+            .to_string())
+    }
+}
+
+fn validate_brightness(brightness: String) -> Result<(), String> {
+    match brightness.parse::<i32>() {
+        Ok(brightness) if (-255..=255).contains(&brightness) => Ok(()),
+        _ => Err("Brightness must be a whole number from -255 to 255".to_string())
+    }
+}
+
+fn validate_contrast(contrast: String) -> Result<(), String> {
+    match contrast.parse::<f32>() {
+        Ok(contrast) if (-100.0..=100.0).contains(&contrast) => Ok(()),
+        _ => Err("Contrast must be a number from -100 to 100".to_string())
+    }
+}
+
+fn validate_hue(hue: String) -> Result<(), String> {
+    match hue.parse::<i32>() {
+        Ok(_) => Ok(()),
+        Err(_) => Err("Hue must be a whole number of degrees".to_string())
+    }
+}
+
+fn validate_gamma(gamma: String) -> Result<(), String> {
+    match gamma.parse::<f32>() {
+        Ok(gamma) if gamma > 0.0 && gamma.is_finite() => Ok(()),
+        _ => Err("Gamma must be a positive number".to_string())
+    }
+}
+
+fn validate_quantize(quantize: String) -> Result<(), String> {
+    match quantize.parse::<u32>() {
+        Ok(quantize) if quantize > 0 => Ok(()),
+        _ => Err("Quantize must be a whole number above 0".to_string())
+    }
+}
+
+fn validate_palette(palette: String) -> Result<(), String> {
+    match palette.parse::<u32>() {
+        Ok(palette) if palette > 0 => Ok(()),
+        _ => Err("Palette size must be a whole number above 0".to_string())
+    }
+}
+
+fn validate_blur(blur: String) -> Result<(), String> {
+    match blur.parse::<f32>() {
+        Ok(blur) if blur > 0.0 && blur.is_finite() => Ok(()),
+        _ => Err("Blur must be a positive number".to_string())
+    }
+}
+
+fn validate_opacity(opacity: String) -> Result<(), String> {
+    match opacity.parse::<f32>() {
+        Ok(opacity) if (0.0..=1.0).contains(&opacity) => Ok(()),
+        _ => Err("Opacity must be a number from 0 to 1".to_string())
     }
 }
 
 fn validate_size(size: String) -> Result<(), String> {
-    if regex::Regex::new("\\d+[Xx]\\d+|term|original").unwrap().is_match(size.as_str()) {
-        return Ok(());
+    if !regex::Regex::new("^(\\d+[Xx]\\d+|\\d+%|term|original)$").unwrap().is_match(size.as_str()) {
+        return Err("Size is not in a valid format".to_string());
+    }
+    match get_size(&size) {
+        Some(Size::Cells(0, _)) | Some(Size::Cells(_, 0)) => Err("Size must have a width and height above 0".to_string()),
+        Some(Size::Percent(0)) => Err("Size must be a percentage above 0".to_string()),
+        Some(_) => Ok(()),
+        // too big to fit in a u32
+        None => Err("Size is too large".to_string())
     }
-    Err("Size is not in a valid format".to_string())
 }
 
-fn get_size(size: &str) -> Option<(u32, u32)> {
-    match size {
-        "term" => {
-            let sz = terminal_size::terminal_size()?;
-            Some(((sz.0).0.into(), (sz.1).0.into()))
-        }
-        "original" => None,
-        sz => {
-            let re = regex::Regex::new("(\\d+)[Xx](\\d+)").unwrap();
-            if re.is_match(sz) {
-                let captures = re.captures(sz).unwrap();
-                Some((captures.get(1).map_or(0, |m| m.as_str().parse().unwrap()),
-                      captures.get(2).map_or(0, |m| m.as_str().parse().unwrap())))
-            } else {
-                let sz = terminal_size::terminal_size()?;
-                Some(((sz.0).0.into(), (sz.1).0.into()))
-            }
+fn validate_svg_pixel_size(size: String) -> Result<(), String> {
+    match size.parse::<u32>() {
+        Ok(size) if size > 0 => Ok(()),
+        _ => Err("Svg pixel size must be a whole number above 0".to_string())
+    }
+}
+
+fn validate_montage(columns: String) -> Result<(), String> {
+    match columns.parse::<usize>() {
+        Ok(columns) if columns > 0 => Ok(()),
+        _ => Err("Montage columns must be a whole number above 0".to_string())
+    }
+}
+
+fn validate_cols(columns: String) -> Result<(), String> {
+    match columns.parse::<u32>() {
+        Ok(columns) if columns > 0 => Ok(()),
+        _ => Err("Columns must be a whole number above 0".to_string())
+    }
+}
+
+fn validate_max_size(size: String) -> Result<(), String> {
+    match size.parse::<u32>() {
+        Ok(size) if size > 0 => Ok(()),
+        _ => Err("Maximum size must be a whole number above 0".to_string())
+    }
+}
+
+fn validate_seed(seed: String) -> Result<(), String> {
+    match seed.parse::<u64>() {
+        Ok(_) => Ok(()),
+        Err(_) => Err("Seed must be a whole number".to_string())
+    }
+}
+
+fn validate_loops(loops: String) -> Result<(), String> {
+    match loops.parse::<u32>() {
+        Ok(_) => Ok(()),
+        Err(_) => Err("Loops must be a whole number".to_string())
+    }
+}
+
+fn validate_format(format: String) -> Result<(), String> {
+    match image::ImageFormat::from_extension(&format) {
+        Some(format) if format.can_write() => Ok(()),
+        Some(format) => Err(format!("Saving {:?} images isn't supported", format)),
+        None => Err("Format must be an image extension like png or jpg".to_string())
+    }
+}
+
+fn validate_quality(quality: String) -> Result<(), String> {
+    match quality.parse::<u8>() {
+        Ok(quality) if (1..=100).contains(&quality) => Ok(()),
+        _ => Err("Quality must be a whole number from 1 to 100".to_string())
+    }
+}
+
+fn validate_radius(radius: String) -> Result<(), String> {
+    match radius.parse::<u32>() {
+        Ok(_) => Ok(()),
+        Err(_) => Err("Radius must be a whole number of pixels".to_string())
+    }
+}
+
+fn validate_indent(indent: String) -> Result<(), String> {
+    match indent.parse::<u32>() {
+        Ok(_) => Ok(()),
+        Err(_) => Err("Indent must be a whole number of spaces".to_string())
+    }
+}
+
+fn validate_timeout(timeout: String) -> Result<(), String> {
+    match timeout.parse::<u64>() {
+        Ok(_) => Ok(()),
+        Err(_) => Err("Timeout must be a whole number of seconds".to_string())
+    }
+}
+
+// cmd.exe and older versions of PowerShell print escapes literally unless virtual terminal processing is on
+#[cfg(windows)]
+fn enable_virtual_terminal() {
+    use winapi::um::consoleapi::{GetConsoleMode, SetConsoleMode};
+    use winapi::um::processenv::GetStdHandle;
+    use winapi::um::winbase::STD_OUTPUT_HANDLE;
+    use winapi::um::wincon::ENABLE_VIRTUAL_TERMINAL_PROCESSING;
+
+    unsafe {
+        let handle = GetStdHandle(STD_OUTPUT_HANDLE);
+        let mut mode = 0;
+        // fails when stdout isn't a console
+        if GetConsoleMode(handle, &mut mode) != 0 {
+            SetConsoleMode(handle, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING);
         }
     }
 }
 
+#[cfg(not(windows))]
+fn enable_virtual_terminal() {}
+
 fn main() {
-    let matches = clap::App::new("image_render")
+    let program = std::env::args().next().unwrap();
+    let usage = format!("{0} [render] [options] <input>...\n    {0} info <input>...\n    {0} convert [options] <input> \
+                         <output>\n    {0} convert [options] --batch <dir> --out <dir>", program);
+    let render_usage = format!("{} [render] [--filters] -f filter [--linear-resize] [--size WxH|N%|term|original] \
+                                [--max-width W] [--max-height H] [--resize-mode mode] [--tile] [--mode mode] \
+                                [--colors colors] [--color-distance metric] \
+                                [--dither method [--dither-matrix N] [--seed N]] [--charset chars] [--char char] \
+                                [--background RRGGBB|--checker [--checker-size N]] [--tonemap method] \
+                                [--simulate deficiency] [--trim [--trim-color RRGGBB]] [--crop X,Y,W,H] [--flip-h] \
+                                [--flip-v] [--rotate degrees] [--grayscale|--sepia] [--brightness N] [--contrast N] \
+                                [--hue degrees] [--invert] [--blur sigma] [--gamma N] [--sharpen[=AMOUNT,THRESHOLD]] \
+                                [--edges] [--binarize level] [--quantize N] [--diff image] \
+                                [--overlay image [--opacity N]] [--mask circle|rounded [--radius N]] \
+                                [--bg-image image] \
+                                [--watermark text [--watermark-pos corner] [--watermark-color RRGGBB]] \
+                                [--threshold N] [--font-ratio ratio] [--detect-font-ratio] [--no-reset] [--center] \
+                                [--indent N] [--border[=RRGGBB]] [--title text] [--svg-pixel-size N] \
+                                [--timeout seconds] [--no-auto-orient] [--color-managed] [--stream] [--clear] \
+                                [--progress] [--animate [--loops N]] [--palette N] [--cols N] \
+                                [--montage columns|--contact-sheet dir] [--output file] [--save-thumbnail file] \
+                                [--split-frames dir] [--no-cache] [--clear-cache] [--line-ending lf|crlf] \
+                                [--no-trailing-newline] [--link] [--time] [-q|-v] <input>...", program);
+    let matches = app(&usage, &render_usage).get_matches_from(args(std::env::args_os().collect()));
+
+    match matches.subcommand() {
+        ("info", Some(matches)) => info(matches),
+        ("convert", Some(matches)) => convert(matches),
+        (_, matches) => render(matches.unwrap())
+    }
+}
+
+// every subcommand and its flags. usage is shown for the whole program and render_usage for render
+fn app<'a>(usage: &'a str, render_usage: &'a str) -> clap::App<'a, 'a> {
+    clap::App::new("image_render")
         .version("1.0")
         .author("Riley Quinn")
         .about("A Rust thing that renders images.")
         .setting(clap::AppSettings::ArgRequiredElseHelp)
-        .usage(format!("{} [--filters] -f filter [--size WxH|term|original] <input> [output]", std::env::args().next().unwrap()).as_str())
-        .arg(Arg::with_name("filters")
-            .long("filters")
-            .help("List all resizing filters"))
-        .arg(Arg::with_name("size")
-            .short("s")
-            .long("size")
-            .default_value("term")
-            .validator(validate_size)
-            .help("Size of output image. Size must be WIDTHxHEIGHT, term, or original"))
-        .arg(Arg::with_name("filter")
-            .short("f")
-            .long("filter")
-            .possible_values(&FILTERS)
-            .default_value("nearest")
-            .help("Filter to use to resize image"))
-        .arg(Arg::with_name("input")
-            .index(1)
-            .required_unless("filters")
-            .help("Input file"))
-        .arg(Arg::with_name("output")
-            .index(2)
-            .default_value("-")
-            .help("Output file. Passing \"-\" will output to stdout"))
-        .get_matches();
+        .usage(usage)
+        .subcommand(SubCommand::with_name("render")
+            .about("Draw images in the terminal. This runs when no subcommand is given")
+            .usage(render_usage)
+            .arg(Arg::with_name("filters")
+                .long("filters")
+                .help("List all resizing filters"))
+            .arg(Arg::with_name("size")
+                .short("s")
+                .long("size")
+                .default_value("term")
+                .validator(validate_size)
+                .help("Size of output image. Size must be WIDTHxHEIGHT, a percentage of the original size, term, or original. \
+                       term uses COLUMNS and LINES or 80x24 when not writing to a terminal. Defaults to original when the output \
+                       isn't a terminal"))
+            .arg(Arg::with_name("max-width")
+                .long("max-width")
+                .takes_value(true)
+                .validator(validate_max_size)
+                .help("Most columns the image can take up, even when the terminal is wider with --size term. The image \
+                       keeps its shape"))
+            .arg(Arg::with_name("max-height")
+                .long("max-height")
+                .takes_value(true)
+                .validator(validate_max_size)
+                .help("Most rows the image can take up. The image keeps its shape"))
+            .arg(Arg::with_name("resize-mode")
+                .long("resize-mode")
+                .possible_values(&RESIZE_MODES)
+                .default_value("fit")
+                .help("How the image is resized to --size. fit keeps its shape inside the size, fill covers the size and \
+                       crops what doesn't fit, and stretch resizes to exactly the size"))
+            .arg(Arg::with_name("tile")
+                .long("tile")
+                .help("Repeat the image across all of --size like wallpaper instead of resizing it"))
+            .arg(Arg::with_name("filter")
+                .short("f")
+                .long("filter")
+                .possible_values(&FILTERS)
+                .default_value("nearest")
+                .help("Filter to use to resize image"))
+            .arg(Arg::with_name("linear-resize")
+                .long("linear-resize")
+                .help("Resize in linear light so shrunk images don't come out darker. Slower"))
+            .arg(Arg::with_name("mode")
+                .short("m")
+                .long("mode")
+                .possible_values(&MODES)
+                .default_value("block")
+                .help("How to draw pixels. halfblock fits two rows of pixels in each line, \
+                       quadrant draws a 2x2 grid of pixels in two colors in each character, ascii uses no escape codes, \
+                       shades draws brightness with the shade blocks \u{2591}\u{2592}\u{2593}\u{2588}, \
+                       braille draws a 2x4 grid of dots in each character, sixel, kitty, and iterm draw real pixels on supported terminals, \
+                       html writes a <pre> block for web pages, svg writes an svg document, \
+                       json writes the size and RGBA value of every pixel"))
+            .arg(Arg::with_name("colors")
+                .short("c")
+                .long("colors")
+                .possible_values(&COLORS)
+                .takes_value(true)
+                .help("Colors supported by the terminal. Detected from COLORTERM and TERM by default. \
+                       Passing this draws with color even if NO_COLOR is set"))
+            .arg(Arg::with_name("color-distance")
+                .long("color-distance")
+                .possible_values(&COLOR_DISTANCES)
+                .default_value("rgb")
+                .help("How the closest color is picked when drawing with 256 or 16 colors. redmean weights the \
+                       difference by how red the colors are, which is closer to how different they look"))
+            .arg(Arg::with_name("dither")
+                .long("dither")
+                .possible_values(&DITHERS)
+                .default_value("none")
+                .help("Smooth gradients when drawing with 256 or 16 colors. floyd uses Floyd-Steinberg error diffusion, \
+                       with noise picked from --seed when it's given, ordered uses a Bayer matrix and always dithers an \
+                       image the same way, and random adds noise picked from --seed"))
+            .arg(Arg::with_name("dither-matrix")
+                .long("dither-matrix")
+                .possible_values(&DITHER_MATRICES)
+                .default_value("4")
+                .help("Size of the Bayer matrix used by --dither ordered"))
+            .arg(Arg::with_name("seed")
+                .long("seed")
+                .takes_value(true)
+                .validator(validate_seed)
+                .help("Seed for the noise of --dither random and floyd. The same seed always gives the same output. \
+                       random uses 0 without one and floyd adds no noise"))
+            .arg(Arg::with_name("charset")
+                .long("charset")
+                .takes_value(true)
+                .validator(validate_charset)
+                .help("Characters used by ascii mode ordered from darkest to lightest. Needs at least two characters that \
+                       are each one column wide"))
+            .arg(Arg::with_name("char")
+                .long("char")
+                .default_value(" ")
+                .validator(validate_cell)
+                .help("Character drawn in each cell of block mode"))
+            .arg(Arg::with_name("background")
+                .long("background")
+                .default_value("000000")
+                .validator(validate_color)
+                .help("Color drawn behind transparent parts of the image"))
+            .arg(Arg::with_name("checker")
+                .long("checker")
+                .help("Draw a checkerboard behind transparent parts of the image instead of --background"))
+            .arg(Arg::with_name("checker-size")
+                .long("checker-size")
+                .default_value("4")
+                .validator(validate_checker_size)
+                .help("Width of each square of --checker in pixels of the resized image"))
+            .arg(Arg::with_name("tonemap")
+                .long("tonemap")
+                .possible_values(&TONEMAPS)
+                .takes_value(true)
+                .help("How 16 bit images are brought down to 8 bits. linear stretches the darkest and brightest \
+                       values to black and white and reinhard compresses highlights. They're scaled evenly by default"))
+            .arg(Arg::with_name("simulate")
+                .long("simulate")
+                .possible_values(&DEFICIENCIES)
+                .takes_value(true)
+                .help("Show the image as someone who is red, green, or blue color blind sees it"))
+            .arg(Arg::with_name("trim")
+                .long("trim")
+                .help("Remove fully transparent borders so the rest of the image fills the size"))
+            .arg(Arg::with_name("trim-color")
+                .long("trim-color")
+                .takes_value(true)
+                .value_name("RRGGBB")
+                .validator(validate_color)
+                .requires("trim")
+                .help("Remove borders of this color with --trim instead of transparent ones"))
+            .arg(Arg::with_name("crop")
+                .long("crop")
+                .takes_value(true)
+                .validator(validate_crop)
+                .help("Only draw the rectangle of the image at X,Y that is WIDTH by HEIGHT pixels"))
+            .arg(Arg::with_name("flip-h")
+                .long("flip-h")
+                .help("Mirror the image left to right"))
+            .arg(Arg::with_name("flip-v")
+                .long("flip-v")
+                .help("Mirror the image top to bottom"))
+            .arg(Arg::with_name("rotate")
+                .long("rotate")
+                .possible_values(&ROTATIONS)
+                .takes_value(true)
+                .help("Degrees to rotate the image clockwise by. Flips are applied first"))
+            .arg(Arg::with_name("grayscale")
+                .long("grayscale")
+                .help("Draw the image in shades of gray"))
+            .arg(Arg::with_name("sepia")
+                .long("sepia")
+                .conflicts_with("grayscale")
+                .help("Draw the image in a sepia tone"))
+            .arg(Arg::with_name("brightness")
+                .long("brightness")
+                .default_value("0")
+                .allow_hyphen_values(true)
+                .validator(validate_brightness)
+                .help("Amount from -255 to 255 added to every color channel"))
+            .arg(Arg::with_name("contrast")
+                .long("contrast")
+                .default_value("0")
+                .allow_hyphen_values(true)
+                .validator(validate_contrast)
+                .help("Percent from -100 to 100 to change the contrast by"))
+            .arg(Arg::with_name("hue")
+                .long("hue")
+                .default_value("0")
+                .allow_hyphen_values(true)
+                .validator(validate_hue)
+                .help("Degrees to rotate the hue of every color by"))
+            .arg(Arg::with_name("invert")
+                .long("invert")
+                .help("Invert the colors of the image"))
+            .arg(Arg::with_name("blur")
+                .long("blur")
+                .takes_value(true)
+                .value_name("sigma")
+                .validator(validate_blur)
+                .help("Gaussian blur applied before resizing. Bigger values blur more"))
+            .arg(Arg::with_name("gamma")
+                .long("gamma")
+                .default_value("1.0")
+                .validator(validate_gamma)
+                .help("Gamma correction applied after resizing. Values above 1 brighten midtones"))
+            .arg(Arg::with_name("sharpen")
+                .long("sharpen")
+                .takes_value(true)
+                .min_values(0)
+                .require_equals(true)
+                .value_name("AMOUNT,THRESHOLD")
+                .validator(validate_sharpen)
+                .help("Sharpen the image after resizing with an unsharp mask. Defaults to 1,0, which undoes most of the \
+                       softening from shrinking. The amount is how much harder edges get, so 2 sharpens twice as much \
+                       as 1, and a threshold from 0 to 255 leaves small differences like noise alone"))
+            .arg(Arg::with_name("edges")
+                .long("edges")
+                .help("Draw the outlines in the image, found with a Sobel filter after resizing"))
+            .arg(Arg::with_name("binarize")
+                .long("binarize")
+                .takes_value(true)
+                .value_name("level")
+                .validator(validate_binarize)
+                .help("Make pixels at least this bright white and the rest black after resizing. Goes well with \
+                       braille and ascii modes for line art"))
+            .arg(Arg::with_name("diff")
+                .long("diff")
+                .takes_value(true)
+                .value_name("image")
+                .help("Compare the input with another image of the same size. Pixels that changed are drawn red and \
+                       the rest are dimmed"))
+            .arg(Arg::with_name("overlay")
+                .long("overlay")
+                .takes_value(true)
+                .value_name("image")
+                .help("Draw another image over the input, stretched to the same size"))
+            .arg(Arg::with_name("opacity")
+                .long("opacity")
+                .default_value("1.0")
+                .validator(validate_opacity)
+                .help("How opaque --overlay is from 0 to 1"))
+            .arg(Arg::with_name("mask")
+                .long("mask")
+                .possible_values(&MASKS)
+                .takes_value(true)
+                .help("Make everything outside a circle or a rectangle with rounded corners transparent so the \
+                       background shows through"))
+            .arg(Arg::with_name("radius")
+                .long("radius")
+                .takes_value(true)
+                .validator(validate_radius)
+                .requires("mask")
+                .help("Pixels the corners of --mask rounded are rounded by [default: an eighth of the shorter side]"))
+            .arg(Arg::with_name("bg-image")
+                .long("bg-image")
+                .takes_value(true)
+                .value_name("image")
+                .help("Draw another image behind the input, stretched to the size it's drawn at, so it shows through \
+                       transparent pixels"))
+            .arg(Arg::with_name("watermark")
+                .long("watermark")
+                .takes_value(true)
+                .value_name("text")
+                .help("Draw text over a corner of the image with a small built in font"))
+            .arg(Arg::with_name("watermark-pos")
+                .long("watermark-pos")
+                .possible_values(&CORNERS)
+                .default_value("bottom-right")
+                .help("Corner --watermark is drawn in"))
+            .arg(Arg::with_name("watermark-color")
+                .long("watermark-color")
+                .default_value("ffffff")
+                .validator(validate_color)
+                .help("Color of --watermark"))
+            .arg(Arg::with_name("quantize")
+                .long("quantize")
+                .takes_value(true)
+                .validator(validate_quantize)
+                .help("Reduce the image to this many colors picked from it, whatever the terminal supports"))
+            .arg(Arg::with_name("threshold")
+                .long("threshold")
+                .default_value("128")
+                .validator(validate_threshold)
+                .help("Minimum brightness of a dot in braille mode"))
+            .arg(Arg::with_name("font-ratio")
+                .long("font-ratio")
+                .default_value("2.0")
+                .validator(validate_font_ratio)
+                .help("Height of a terminal cell divided by its width"))
+            .arg(Arg::with_name("detect-font-ratio")
+                .long("detect-font-ratio")
+                .help("Ask the terminal how big its cells are and use that instead of --font-ratio when it answers"))
+            .arg(Arg::with_name("no-reset")
+                .long("no-reset")
+                .help("Only reset colors at the end of each line instead of after every cell to make halfblock output \
+                       smaller"))
+            .arg(Arg::with_name("center")
+                .long("center")
+                .help("Draw the image in the middle of the terminal. Ignored when the output isn't the terminal"))
+            .arg(Arg::with_name("indent")
+                .long("indent")
+                .takes_value(true)
+                .validator(validate_indent)
+                .help("Put this many spaces before every line of the image. With --center they're added to the \
+                       padding that centers it"))
+            .arg(Arg::with_name("progress")
+                .long("progress")
+                .help("Show a progress bar on stderr while block, halfblock, quadrant, ascii, shades, and braille modes draw"))
+            .arg(Arg::with_name("border")
+                .long("border")
+                .takes_value(true)
+                .min_values(0)
+                .require_equals(true)
+                .value_name("RRGGBB")
+                .validator(validate_color)
+                .help("Draw a frame around the image, optionally in a color like --border=ff0000. The color is left \
+                       out when NO_COLOR is set. Only works with block, halfblock, quadrant, ascii, shades, and braille \
+                       modes"))
+            .arg(Arg::with_name("title")
+                .long("title")
+                .takes_value(true)
+                .value_name("text")
+                .help("Draw text in the middle of a line above the image. Text wider than the image is cut off"))
+            .arg(Arg::with_name("svg-pixel-size")
+                .long("svg-pixel-size")
+                .default_value("10")
+                .validator(validate_svg_pixel_size)
+                .help("Width and height of each pixel in svg mode"))
+            .arg(Arg::with_name("timeout")
+                .long("timeout")
+                .default_value("30")
+                .validator(validate_timeout)
+                .help("Seconds to wait when downloading the input from a URL"))
+            .arg(Arg::with_name("auto-orient")
+                .long("auto-orient")
+                .overrides_with("no-auto-orient")
+                .help("Rotate and flip the image based on its EXIF orientation. This is the default"))
+            .arg(Arg::with_name("no-auto-orient")
+                .long("no-auto-orient")
+                .overrides_with("auto-orient")
+                .help("Ignore the EXIF orientation of the image"))
+            .arg(Arg::with_name("color-managed")
+                .long("color-managed")
+                .help("Convert PNGs and JPEGs with an embedded ICC profile like Display P3 or Adobe RGB to sRGB before \
+                       drawing them. Images without a profile are already sRGB. Other profiles, like LUT based ones, \
+                       are warned about and drawn as sRGB. Needs building with --features color-management"))
+            .arg(Arg::with_name("stream")
+                .long("stream")
+                .help("Shrink PNGs while they're decoded so big images don't need much memory. 16 bit PNGs are shrunk \
+                       to 8 bits. Other formats and interlaced PNGs are decoded normally. Only works when --size is \
+                       WIDTHxHEIGHT or term and there is no --crop"))
+            .arg(Arg::with_name("clear")
+                .long("clear")
+                .help("Clear the terminal before drawing. Animations are always drawn from the top of the screen so this \
+                       keeps them from covering what was already there. Ignored when not writing to a terminal"))
+            .arg(Arg::with_name("animate")
+                .long("animate")
+                .help("Play all frames of animated GIFs and PNGs. In a terminal space pauses, the left and right \
+                       arrows step through frames while paused, and q quits. Output that isn't a terminal just loops"))
+            .arg(Arg::with_name("loops")
+                .long("loops")
+                .default_value("0")
+                .validator(validate_loops)
+                .help("Times to play animations. 0 plays forever"))
+            .arg(Arg::with_name("montage")
+                .long("montage")
+                .takes_value(true)
+                .validator(validate_montage)
+                .help("Arrange the inputs in a grid this many images wide. Animations show their first frame"))
+            .arg(Arg::with_name("palette")
+                .long("palette")
+                .takes_value(true)
+                .value_name("N")
+                .validator(validate_palette)
+                .conflicts_with_all(&["animate", "montage", "contact-sheet", "split-frames"])
+                .help("Print the N most common colors in the image with their hex codes instead of drawing it"))
+            .arg(Arg::with_name("contact-sheet")
+                .long("contact-sheet")
+                .takes_value(true)
+                .value_name("dir")
+                .conflicts_with_all(&["input", "montage"])
+                .help("Draw every image in a directory in a grid with its file name above it. Files that aren't \
+                       images are skipped"))
+            .arg(Arg::with_name("cols")
+                .long("cols")
+                .takes_value(true)
+                .value_name("N")
+                .validator(validate_cols)
+                .help("Most columns the image can take up, like --max-width. With --contact-sheet it's the images in \
+                       each row instead [default: 4]"))
+            .arg(Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .default_value("-")
+                .help("Output file. Passing \"-\" will output to stdout. Paths ending in an image extension like .png \
+                       save the resized image instead"))
+            .arg(Arg::with_name("split-frames")
+                .long("split-frames")
+                .takes_value(true)
+                .value_name("dir")
+                .help("Save every frame of an animated GIF or PNG to this directory as frame_0001.png and so on \
+                       instead of drawing it. Frames are resized like --output images"))
+            .arg(Arg::with_name("save-thumbnail")
+                .long("save-thumbnail")
+                .takes_value(true)
+                .value_name("file")
+                .help("Also save the resized image to this file as a PNG"))
+            .arg(Arg::with_name("quiet")
+                .short("q")
+                .long("quiet")
+                .conflicts_with("verbose")
+                .help("Don't print errors. The exit status still says what went wrong"))
+            .arg(Arg::with_name("verbose")
+                .short("v")
+                .long("verbose")
+                .help("Print the size of each image and how long it took to decode and draw"))
+            .arg(Arg::with_name("link")
+                .long("link")
+                .help("Make the image a hyperlink to the file or URL it came from in terminals that support OSC 8. \
+                       Ignored when not writing to a terminal"))
+            .arg(Arg::with_name("time")
+                .long("time")
+                .help("Print how long each image took to decode, resize, and render on stderr"))
+            .arg(Arg::with_name("line-ending")
+                .long("line-ending")
+                .possible_values(&["lf", "crlf"])
+                .default_value("lf")
+                .help("Characters written at the end of each line. crlf is for output read on Windows"))
+            .arg(Arg::with_name("no-cache")
+                .long("no-cache")
+                .help("Decode and resize images even when they were drawn the same way before. Images drawn at a \
+                       WIDTHxHEIGHT or term size are kept in $XDG_CACHE_HOME/image_render, up to 64 MiB of them"))
+            .arg(Arg::with_name("clear-cache")
+                .long("clear-cache")
+                .help("Delete every image kept in the cache before drawing anything"))
+            .arg(Arg::with_name("no-trailing-newline")
+                .long("no-trailing-newline")
+                .help("Leave out the newline after the last line so the output can be embedded in other text"))
+            .arg(Arg::with_name("input")
+                .index(1)
+                .multiple(true)
+                .required_unless_one(&["filters", "contact-sheet", "clear-cache"])
+                .help("Input files or URLs drawn one after another. Passing \"-\" will read from stdin. A second input \
+                       that doesn't exist is taken as the output like --output, which is deprecated")))
+        .subcommand(SubCommand::with_name("info")
+            .about("Print the format, size, color type, bit depth, and whether images are animated without drawing \
+                    them")
+            .arg(Arg::with_name("json")
+                .long("json")
+                .help("Print a JSON object on each line instead"))
+            .arg(Arg::with_name("timeout")
+                .long("timeout")
+                .default_value("30")
+                .validator(validate_timeout)
+                .help("Seconds to wait when downloading the input from a URL"))
+            .arg(Arg::with_name("input")
+                .index(1)
+                .multiple(true)
+                .required(true)
+                .help("Input files or URLs. Passing \"-\" will read from stdin")))
+        .subcommand(SubCommand::with_name("convert")
+            .about("Resize an image and save it to a file in the format its extension names")
+            .arg(Arg::with_name("format")
+                .long("format")
+                .takes_value(true)
+                .validator(validate_format)
+                .help("Format to save in as an extension like png or jpg. Guessed from the output's extension by \
+                       default"))
+            .arg(Arg::with_name("quality")
+                .long("quality")
+                .takes_value(true)
+                .validator(validate_quality)
+                .help("JPEG quality from 1 to 100 [default: 75]"))
+            .arg(Arg::with_name("size")
+                .short("s")
+                .long("size")
+                .default_value("original")
+                .validator(validate_size)
+                .help("Size of the saved image. Size must be WIDTHxHEIGHT in pixels, a percentage of the original \
+                       size, or original"))
+            .arg(Arg::with_name("max-width")
+                .long("max-width")
+                .takes_value(true)
+                .validator(validate_max_size)
+                .help("Most pixels wide the image can be. The image keeps its shape"))
+            .arg(Arg::with_name("max-height")
+                .long("max-height")
+                .takes_value(true)
+                .validator(validate_max_size)
+                .help("Most pixels tall the image can be. The image keeps its shape"))
+            .arg(Arg::with_name("resize-mode")
+                .long("resize-mode")
+                .possible_values(&RESIZE_MODES)
+                .default_value("fit")
+                .help("How the image is resized to --size. fit keeps its shape inside the size, fill covers the size \
+                       and crops what doesn't fit, and stretch resizes to exactly the size"))
+            .arg(Arg::with_name("filter")
+                .short("f")
+                .long("filter")
+                .possible_values(&FILTERS)
+                .default_value("nearest")
+                .help("Filter to use to resize image"))
+            .arg(Arg::with_name("linear-resize")
+                .long("linear-resize")
+                .help("Resize in linear light so shrunk images don't come out darker. Slower"))
+            .arg(Arg::with_name("timeout")
+                .long("timeout")
+                .default_value("30")
+                .validator(validate_timeout)
+                .help("Seconds to wait when downloading the input from a URL"))
+            .arg(Arg::with_name("no-auto-orient")
+                .long("no-auto-orient")
+                .help("Ignore the EXIF orientation of the image"))
+            .arg(Arg::with_name("color-managed")
+                .long("color-managed")
+                .help("Convert images with an RGB matrix ICC profile like Display P3 to sRGB. Needs building with \
+                       --features color-management"))
+            .arg(Arg::with_name("batch")
+                .long("batch")
+                .takes_value(true)
+                .value_name("dir")
+                .requires("out")
+                .conflicts_with_all(&["input", "output"])
+                .help("Resize every image in this directory instead of one input. Files that aren't images are \
+                       skipped"))
+            .arg(Arg::with_name("out")
+                .long("out")
+                .takes_value(true)
+                .value_name("dir")
+                .requires("batch")
+                .help("Directory --batch saves images to with the same names"))
+            .arg(Arg::with_name("recursive")
+                .long("recursive")
+                .requires("batch")
+                .help("Resize images in the subdirectories of --batch too. They're saved in the same subdirectories \
+                       of --out"))
+            .arg(Arg::with_name("input")
+                .index(1)
+                .required_unless("batch")
+                .help("Input file or URL. Passing \"-\" will read from stdin"))
+            .arg(Arg::with_name("output")
+                .index(2)
+                .required_unless("batch")
+                .help("File to save to. The extension picks the format, like .png or .jpg")))
+}
+
+// subcommands and the flags of the whole program. anything else is the start of a render
+static COMMANDS: [&str; 8] = ["render", "info", "convert", "help", "-h", "--help", "-V", "--version"];
+
+// image_render <input> still draws the input like before there were subcommands
+fn args(mut args: Vec<OsString>) -> Vec<OsString> {
+    if args.len() > 1 && !COMMANDS.iter().any(|&command| args[1] == command) {
+        args.insert(1, OsString::from("render"));
+    }
+    args
+}
+
+fn info(matches: &ArgMatches) {
+    let timeout = Duration::from_secs(matches.value_of("timeout").unwrap().parse().unwrap());
+
+    // a failed input is reported and skipped. the exit status is for the last failure
+    let mut status = 0;
+    for input in matches.values_of("input").unwrap() {
+        match image_info(input, timeout) {
+            Ok(info) if matches.is_present("json") => println!("{}", info_json(input, &info)),
+            Ok(info) => {
+                println!("{}", input);
+                println!("  format: {}", format!("{:?}", info.format).to_uppercase());
+                println!("  size: {}x{}", info.width, info.height);
+                println!("  color: {}", color_name(info.color));
+                println!("  bit depth: {}", info.bit_depth);
+                println!("  animated: {}", if info.animated { "yes" } else { "no" });
+            }
+            Err(e) => {
+                eprintln!("{}: {}", input, e);
+                status = EXIT_INPUT;
+            }
+        }
+    }
+
+    if status != 0 {
+        std::process::exit(status);
+    }
+}
+
+fn color_name(color: ColorType) -> &'static str {
+    match color {
+        ColorType::L8 | ColorType::L16 => "grayscale",
+        ColorType::La8 | ColorType::La16 => "grayscale with alpha",
+        ColorType::Rgb8 | ColorType::Rgb16 => "RGB",
+        ColorType::Rgba8 | ColorType::Rgba16 => "RGBA",
+        ColorType::Bgr8 => "BGR",
+        ColorType::Bgra8 => "BGRA",
+        _ => "unknown"
+    }
+}
 
+// a JSON string with quotes and control characters escaped
+fn json_string(text: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c)
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn info_json(input: &str, info: &ImageInfo) -> String {
+    format!("{{\"input\": {}, \"format\": \"{}\", \"width\": {}, \"height\": {}, \"color\": \"{}\", \"bit_depth\": {}, \
+             \"animated\": {}}}",
+            json_string(input), format!("{:?}", info.format).to_lowercase(), info.width, info.height,
+            color_name(info.color).to_lowercase(), info.bit_depth, info.animated)
+}
+
+// what an image drawn from input links to with --link. URLs link to themselves and files to a file:// URI of their
+// absolute path. stdin has nothing to link to
+fn link_target(input: &str) -> Option<String> {
+    if input.starts_with("http://") || input.starts_with("https://") {
+        return Some(input.to_string());
+    }
+    if input == "-" {
+        return None;
+    }
+
+    let path = std::fs::canonicalize(input).ok()?;
+    // anything that isn't allowed in a URI path is percent-encoded
+    let encoded: String = path.to_string_lossy().bytes().map(|b| match b {
+        b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => char::from(b).to_string(),
+        _ => format!("%{:02X}", b)
+    }).collect();
+    Some(format!("file://{}", encoded))
+}
+
+// OSC 8 starts a link to uri with no parameters. everything written until LINK_END is clickable
+fn link_start(uri: &str) -> String {
+    format!("\x1b]8;;{}\x07", uri)
+}
+
+// a link with an empty URI closes the one that's open
+static LINK_END: &[u8] = b"\x1b]8;;\x07";
+
+// before --output the second of two inputs was the output. it still is when it can't be read as an input
+fn legacy_output(input: &str) -> bool {
+    input != "-" && !input.starts_with("http://") && !input.starts_with("https://") && !Path::new(input).exists()
+}
+
+// every file in dir, and in its subdirectories when recursive, in name order
+fn list_files(dir: &Path, recursive: bool) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() && recursive {
+            files.extend(list_files(&path, recursive)?);
+        } else if path.is_file() {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+// the format an image is saved in. --format overrides the extension of the output
+fn output_format(output: &Path, format: Option<&str>) -> Result<image::ImageFormat, String> {
+    match format.map_or_else(|| image::ImageFormat::from_path(output).ok(), image::ImageFormat::from_extension) {
+        Some(format) if format.can_write() => Ok(format),
+        Some(format) => Err(format!("Saving {:?} images isn't supported", format)),
+        None => Err(format!("{}: the extension doesn't name an image format", output.display()))
+    }
+}
+
+// the size images are drawn at when size is the --size that was given. files and pipes have nothing to do with the
+// size of the terminal so they're only resized when asked to. thumbnails in a contact sheet are small no matter where
+// they're drawn
+fn render_size(size: Option<&str>, to_terminal: bool, contact_sheet: bool, split_frames: bool) -> Size {
+    match size {
+        Some(size) => get_size(size).unwrap(),
+        None if contact_sheet => Size::Cells(20, 10),
+        None if !to_terminal || split_frames => Size::Original,
+        None => get_size("term").unwrap()
+    }
+}
+
+// how long each step of drawing an image took for --time
+fn timings(decoded: Duration, resized: Duration, drawn: Duration, from_cache: bool) -> String {
+    let ms = |time: Duration| time.as_secs_f64() * 1000.0;
+    let cached = if from_cache { " (from the cache)" } else { "" };
+    format!("decode {:.1}ms, resize {:.1}ms, render {:.1}ms{}", ms(decoded), ms(resized), ms(drawn), cached)
+}
+
+// how a batch went. the exit status is for the last failure
+struct Batch {
+    resized: usize,
+    skipped: usize,
+    status: i32
+}
+
+// resizes every image in dir into the same place under --out. a file that can't be saved is reported and skipped
+fn convert_batch(matches: &ArgMatches, dir: &str, options: &RenderOptions, quality: Option<u8>) -> Batch {
+    let verbosity = Verbosity::Normal;
+    let timeout = Duration::from_secs(matches.value_of("timeout").unwrap().parse().unwrap());
+    let auto_orient = !matches.is_present("no-auto-orient");
+    let color_managed = matches.is_present("color-managed");
+
+    let out = Path::new(matches.value_of("out").unwrap());
+    let files = match list_files(Path::new(dir), matches.is_present("recursive")) {
+        Ok(files) => files,
+        Err(e) => err_and_exit!(verbosity, EXIT_INPUT, format!("{}: {}", dir, e))
+    };
+
+    let mut batch = Batch { resized: 0, skipped: 0, status: 0 };
+    for input in files {
+        let mut output = out.join(input.strip_prefix(dir).unwrap());
+        if let Some(format) = matches.value_of("format") {
+            output.set_extension(format);
+        }
+
+        // other files in the directory aren't a failure
+        let loaded = load_image(&input.to_string_lossy(), timeout, auto_orient, color_managed).and_then(|img| {
+            output_format(&output, matches.value_of("format")).map(|format| (img, format))
+        });
+        let (img, format) = match loaded {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                eprintln!("{}: skipped: {}", input.display(), e);
+                batch.skipped += 1;
+                continue;
+            }
+        };
+        if color_managed && unconverted_profile(&input.to_string_lossy()) {
+            eprintln!("{}: {}", input.display(), UNCONVERTED_PROFILE);
+        }
+
+        let saved = resize_image(&img, options).and_then(|img| {
+            std::fs::create_dir_all(output.parent().unwrap()).map_err(|e| e.to_string())?;
+            save_image(&img, &output, format, quality)
+        });
+        match saved {
+            Ok(()) => batch.resized += 1,
+            Err(e) => {
+                eprintln!("{}: {}", output.display(), e);
+                batch.status = EXIT_OUTPUT;
+            }
+        }
+    }
+    batch
+}
+
+fn convert(matches: &ArgMatches) {
+    let verbosity = Verbosity::Normal;
+    let timeout = Duration::from_secs(matches.value_of("timeout").unwrap().parse().unwrap());
+    let quality: Option<u8> = matches.value_of("quality").map(|quality| quality.parse().unwrap());
+
+    let options = RenderOptions {
+        size: get_size(matches.value_of("size").unwrap()).unwrap(),
+        max_width: matches.value_of("max-width").map(|width| width.parse().unwrap()),
+        max_height: matches.value_of("max-height").map(|height| height.parse().unwrap()),
+        resize_mode: get_resize_mode(matches.value_of("resize-mode").unwrap()).unwrap(),
+        filter: get_filter(matches.value_of("filter").unwrap()).unwrap(),
+        linear_resize: matches.is_present("linear-resize"),
+        ..RenderOptions::default()
+    };
+
+    let auto_orient = !matches.is_present("no-auto-orient");
+    let color_managed = matches.is_present("color-managed");
+
+    if color_managed && cfg!(not(feature = "color-management")) {
+        err_and_exit!(verbosity, EXIT_ERROR, "--color-managed requires building with --features color-management")
+    }
+
+    if let Some(dir) = matches.value_of("batch") {
+        let out = Path::new(matches.value_of("out").unwrap());
+        let Batch { resized, skipped, status } = convert_batch(matches, dir, &options, quality);
+        println!("Resized {} image{} into {}{}", resized, if resized == 1 { "" } else { "s" }, out.display(),
+                 if skipped > 0 { format!(", skipped {}", skipped) } else { String::new() });
+        if status != 0 {
+            std::process::exit(status);
+        }
+        return;
+    }
+
+    let input = matches.value_of("input").unwrap();
+    let output = matches.value_of("output").unwrap();
+    let format = match output_format(Path::new(output), matches.value_of("format")) {
+        Ok(format) => format,
+        Err(e) => err_and_exit!(verbosity, EXIT_OUTPUT, e)
+    };
+    if quality.is_some() && format != image::ImageFormat::Jpeg {
+        err_and_exit!(verbosity, EXIT_ERROR, "--quality only works with JPEG")
+    }
+
+    let img = match load_image(input, timeout, auto_orient, color_managed) {
+        Ok(img) => img,
+        Err(e) => err_and_exit!(verbosity, EXIT_INPUT, format!("{}: {}", input, e))
+    };
+    if color_managed && unconverted_profile(input) {
+        report!(verbosity, "{}: {}", input, UNCONVERTED_PROFILE);
+    }
+    let img = match resize_image(&img, &options) {
+        Ok(img) => img,
+        Err(e) => err_and_exit!(verbosity, EXIT_ERROR, format!("{}: {}", input, e))
+    };
+    if let Err(e) = save_image(&img, output, format, quality) {
+        err_and_exit!(verbosity, EXIT_OUTPUT, format!("{}: {}", output, e))
+    }
+}
+
+fn render(matches: &ArgMatches) {
     if matches.is_present("filters") {
         for filter in FILTERS.iter() {
             println!("{}", filter);
@@ -98,50 +1126,596 @@ fn main() {
         return;
     }
 
-    let mut img = match image::open(matches.value_of("input").unwrap()) {
-        Ok(img) => img,
-        Err(e) => err_and_return!(e)
+    let verbosity = if matches.is_present("quiet") {
+        Verbosity::Quiet
+    } else if matches.is_present("verbose") {
+        Verbosity::Verbose
+    } else {
+        Verbosity::Normal
+    };
+
+    let cache = if matches.is_present("no-cache") { None } else { Cache::open() };
+    if matches.is_present("clear-cache") {
+        if let Err(e) = Cache::open().map_or(Ok(()), |cache| cache.clear()) {
+            err_and_exit!(verbosity, EXIT_OUTPUT, e)
+        }
+        if !matches.is_present("input") {
+            return;
+        }
+    }
+
+    let timeout = Duration::from_secs(matches.value_of("timeout").unwrap().parse().unwrap());
+
+    let contact_sheet = matches.value_of("contact-sheet");
+    // a contact sheet is a montage of everything in the directory in name order
+    let listed: Vec<String> = match contact_sheet {
+        Some(dir) => match list_files(Path::new(dir), false) {
+            Ok(paths) => paths.iter().map(|path| path.to_string_lossy().into_owned()).collect(),
+            Err(e) => err_and_exit!(verbosity, EXIT_INPUT, format!("{}: {}", dir, e))
+        },
+        // --clear-cache can be passed without any inputs
+        None => matches.values_of("input").into_iter().flatten().map(String::from).collect()
+    };
+    let mut inputs: Vec<&str> = listed.iter().map(String::as_str).collect();
+    let positional_output = if contact_sheet.is_none() && matches.occurrences_of("output") == 0 && inputs.len() == 2
+        && legacy_output(inputs[1]) {
+        report!(verbosity, "{}: passing the output after the input is deprecated, use --output instead", inputs[1]);
+        inputs.pop()
+    } else {
+        None
+    };
+    let auto_orient = !matches.is_present("no-auto-orient");
+    let color_managed = matches.is_present("color-managed");
+    if color_managed && cfg!(not(feature = "color-management")) {
+        err_and_exit!(verbosity, EXIT_ERROR, "--color-managed requires building with --features color-management")
+    }
+    let montage_columns: Option<usize> = match contact_sheet {
+        Some(_) => Some(matches.value_of("cols").map_or(4, |columns| columns.parse().unwrap())),
+        None => matches.value_of("montage").map(|columns| columns.parse().unwrap())
     };
+    let animate = matches.is_present("animate") && montage_columns.is_none();
 
     let filter = get_filter(matches.value_of("filter").unwrap()).unwrap();
 
-    // assume font ratio of 1:2.5
-    // I may add something to deal with other ratios later
-    img = img.resize_exact(img.width() * 3, img.height(), filter);
+    // https://no-color.org
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty())
+        && !matches.is_present("colors");
 
-    if let Some(size) = get_size(matches.value_of("size").unwrap()) {
-        img = img.resize(size.0, size.1, filter);
+    let mut mode = get_mode(matches.value_of("mode").unwrap()).unwrap();
+    if no_color && mode.uses_color() {
+        mode = Mode::Ascii;
+    }
+    let colors = match matches.value_of("colors") {
+        Some(colors) => get_colors(colors).unwrap(),
+        None => detect_colors()
+    };
+    let dither = get_dither(matches.value_of("dither").unwrap()).unwrap();
+    let dither_matrix: u32 = matches.value_of("dither-matrix").unwrap().parse().unwrap();
+    let dither_seed: Option<u64> = matches.value_of("seed").map(|seed| seed.parse().unwrap());
+    let charset: Vec<char> = matches.value_of("charset").unwrap_or(DEFAULT_CHARSET).chars().collect();
+    let threshold: u8 = matches.value_of("threshold").unwrap().parse().unwrap();
+    let cell = matches.value_of("char").unwrap().to_string();
+    let background = parse_color(matches.value_of("background").unwrap()).unwrap();
+    let font_ratio: f32 = matches.value_of("font-ratio").unwrap().parse().unwrap();
+    // terminals that don't answer keep the configured ratio
+    let font_ratio = if matches.is_present("detect-font-ratio") {
+        detect_font_ratio().unwrap_or(font_ratio)
+    } else {
+        font_ratio
+    };
+    let loops: u32 = matches.value_of("loops").unwrap().parse().unwrap();
+
+    let output = positional_output.unwrap_or_else(|| matches.value_of("output").unwrap());
+    let to_terminal = output == "-" && stdout().is_terminal();
+    let split_frames = matches.value_of("split-frames");
+
+    let given_size = matches.value_of("size").filter(|_| matches.occurrences_of("size") > 0);
+    let size = render_size(given_size, to_terminal, contact_sheet.is_some(), split_frames.is_some());
+
+    let options = RenderOptions {
+        mode,
+        colors,
+        color_distance: get_color_distance(matches.value_of("color-distance").unwrap()).unwrap(),
+        dither,
+        dither_matrix,
+        dither_seed,
+        size,
+        // --cols is the same limit outside of contact sheets. the smaller of the two wins
+        max_width: matches.values_of("max-width").into_iter().flatten()
+            .chain(matches.value_of("cols").filter(|_| contact_sheet.is_none()))
+            .map(|width| width.parse().unwrap())
+            .min(),
+        max_height: matches.value_of("max-height").map(|height| height.parse().unwrap()),
+        resize_mode: get_resize_mode(matches.value_of("resize-mode").unwrap()).unwrap(),
+        tile: matches.is_present("tile"),
+        filter,
+        linear_resize: matches.is_present("linear-resize"),
+        font_ratio,
+        charset,
+        threshold,
+        cell,
+        background,
+        checker: if matches.is_present("checker") {
+            Some(matches.value_of("checker-size").unwrap().parse().unwrap())
+        } else {
+            None
+        },
+        no_reset: matches.is_present("no-reset"),
+        // a file isn't drawn in the middle of the terminal it's viewed in later
+        center: matches.is_present("center") && to_terminal,
+        indent: matches.value_of("indent").map_or(0, |indent| indent.parse().unwrap()),
+        progress: matches.is_present("progress"),
+        border: matches.is_present("border"),
+        border_color: matches.value_of("border").filter(|_| !no_color).map(|color| parse_color(color).unwrap()),
+        title: matches.value_of("title").map(String::from),
+        diff: matches.value_of("diff").map(|other| match load_image(other, timeout, auto_orient, color_managed) {
+            Ok(other) => other,
+            Err(e) => err_and_exit!(verbosity, EXIT_INPUT, format!("{}: {}", other, e))
+        }),
+        overlay: matches.value_of("overlay").map(|top| match load_image(top, timeout, auto_orient, color_managed) {
+            Ok(top) => top,
+            Err(e) => err_and_exit!(verbosity, EXIT_INPUT, format!("{}: {}", top, e))
+        }),
+        mask: matches.value_of("mask").map(|mask| get_mask(mask).unwrap()),
+        radius: matches.value_of("radius").map(|radius| radius.parse().unwrap()),
+        bg_image: matches.value_of("bg-image").map(|back| match load_image(back, timeout, auto_orient, color_managed) {
+            Ok(back) => back,
+            Err(e) => err_and_exit!(verbosity, EXIT_INPUT, format!("{}: {}", back, e))
+        }),
+        opacity: matches.value_of("opacity").unwrap().parse().unwrap(),
+        watermark: matches.value_of("watermark").map(String::from),
+        watermark_corner: get_corner(matches.value_of("watermark-pos").unwrap()).unwrap(),
+        watermark_color: parse_color(matches.value_of("watermark-color").unwrap()).unwrap(),
+        svg_pixel_size: matches.value_of("svg-pixel-size").unwrap().parse().unwrap(),
+        tonemap: matches.value_of("tonemap").map(|tonemap| get_tonemap(tonemap).unwrap()),
+        simulate: matches.value_of("simulate").map(|deficiency| get_deficiency(deficiency).unwrap()),
+        trim: matches.is_present("trim"),
+        trim_color: matches.value_of("trim-color").map(|color| parse_color(color).unwrap()),
+        crop: matches.value_of("crop").map(|crop| parse_crop(crop).unwrap()),
+        flip_h: matches.is_present("flip-h"),
+        flip_v: matches.is_present("flip-v"),
+        rotate: matches.value_of("rotate").map_or(0, |rotate| rotate.parse().unwrap()),
+        grayscale: matches.is_present("grayscale"),
+        brightness: matches.value_of("brightness").unwrap().parse().unwrap(),
+        contrast: matches.value_of("contrast").unwrap().parse().unwrap(),
+        hue: matches.value_of("hue").unwrap().parse().unwrap(),
+        invert: matches.is_present("invert"),
+        blur: matches.value_of("blur").map(|blur| blur.parse().unwrap()),
+        sepia: matches.is_present("sepia"),
+        gamma: matches.value_of("gamma").unwrap().parse().unwrap(),
+        // --sharpen without a value uses the defaults
+        sharpen: if matches.is_present("sharpen") {
+            Some(parse_sharpen(matches.value_of("sharpen").unwrap_or("1,0")).unwrap())
+        } else {
+            None
+        },
+        edges: matches.is_present("edges"),
+        binarize: matches.value_of("binarize").map(|level| level.parse().unwrap()),
+        quantize: matches.value_of("quantize").map(|quantize| quantize.parse().unwrap())
+    };
+
+    if contact_sheet.is_some() && !mode.is_text() {
+        err_and_exit!(verbosity, EXIT_ERROR, "--contact-sheet only works with block, halfblock, quadrant, ascii, shades, and braille modes")
+    }
+
+    if montage_columns.is_some() && !mode.is_text() {
+        err_and_exit!(verbosity, EXIT_ERROR, "--montage only works with block, halfblock, quadrant, ascii, shades, and braille modes")
+    }
+
+    if options.border && !mode.is_text() {
+        err_and_exit!(verbosity, EXIT_ERROR, "--border only works with block, halfblock, quadrant, ascii, shades, and braille modes")
+    }
+
+    if matches.is_present("seed") && dither != Dither::Random && dither != Dither::Floyd {
+        err_and_exit!(verbosity, EXIT_ERROR, "--seed only works with --dither floyd and random")
+    }
+
+    if options.title.is_some() && mode.is_document() {
+        err_and_exit!(verbosity, EXIT_ERROR, "--title doesn't work with html, svg, and json modes")
     }
 
-    match matches.value_of("output") {
-        Some("-") => write_image(img, &mut stdout()),
-        Some(output) => write_image(img, &mut match std::fs::File::with_options()
+    let thumbnail = matches.value_of("save-thumbnail");
+    let palette: Option<u32> = matches.value_of("palette").map(|palette| palette.parse().unwrap());
+
+    // --stream shrinks images while decoding when the size they're drawn at is known up front
+    let load = |input: &str| match largest_size(&options) {
+        Some(size) if matches.is_present("stream") => load_image_streamed(input, timeout, auto_orient, color_managed, size),
+        _ => load_image(input, timeout, auto_orient, color_managed)
+    };
+
+    if thumbnail.is_some() && inputs.len() > 1 {
+        err_and_exit!(verbosity, EXIT_ERROR, "Only one input can be saved as a thumbnail")
+    }
+
+    // writing to a path like out.png saves the resized image instead of drawing it
+    if let Ok(format) = image::ImageFormat::from_path(output) {
+        if !format.can_write() {
+            err_and_exit!(verbosity, EXIT_OUTPUT, format!("Saving {:?} images isn't supported", format))
+        }
+        if inputs.len() > 1 {
+            err_and_exit!(verbosity, EXIT_ERROR, "Only one input can be saved to an image file")
+        }
+
+        let img = match load(inputs[0]) {
+            Ok(img) => img,
+            Err(e) => err_and_exit!(verbosity, EXIT_INPUT, format!("{}: {}", inputs[0], e))
+        };
+        let img = match resize_image(&img, &options) {
+            Ok(img) => img,
+            Err(e) => err_and_exit!(verbosity, EXIT_ERROR, format!("{}: {}", inputs[0], e))
+        };
+        if let Err(e) = save_image(&img, output, format, None) {
+            err_and_exit!(verbosity, EXIT_OUTPUT, e)
+        }
+        return;
+    }
+
+    // every frame of an animation is saved as DIR/frame_0001.png and so on instead of being drawn
+    if let Some(dir) = split_frames {
+        if inputs.len() > 1 {
+            err_and_exit!(verbosity, EXIT_ERROR, "Only one input can be split into frames")
+        }
+
+        let frames = match load_frames(inputs[0], timeout, auto_orient, color_managed) {
+            Ok(frames) => frames,
+            Err(e) => err_and_exit!(verbosity, EXIT_INPUT, format!("{}: {}", inputs[0], e))
+        };
+        let frames = match frames.iter().map(|(frame, _)| resize_image(frame, &options)).collect::<Result<Vec<_>, _>>() {
+            Ok(frames) => frames,
+            Err(e) => err_and_exit!(verbosity, EXIT_ERROR, format!("{}: {}", inputs[0], e))
+        };
+        if let Err(e) = save_frames(&frames, dir) {
+            err_and_exit!(verbosity, EXIT_OUTPUT, e)
+        }
+        println!("Wrote {} frame{} to {}", frames.len(), if frames.len() == 1 { "" } else { "s" }, dir);
+        return;
+    }
+
+    let mut out: Box<dyn Write> = match output {
+        "-" => {
+            enable_virtual_terminal();
+            Box::new(stdout())
+        }
+        output => match std::fs::OpenOptions::new()
             .write(true)
             .create(true)
             .truncate(true)
             .open(output) {
-            Ok(f) => f,
-            Err(e) => err_and_return!(e)
-        }),
-        _ => write_image(img, &mut stdout())
+            Ok(f) => Box::new(f),
+            Err(e) => err_and_exit!(verbosity, EXIT_OUTPUT, e)
+        }
     };
+
+    if matches.value_of("line-ending") == Some("crlf") {
+        out = Box::new(Crlf(out));
+    }
+
+    if matches.is_present("no-trailing-newline") {
+        out = Box::new(NoTrailingNewline::new(out));
+    }
+
+    // files shouldn't be filled with control codes
+    if matches.is_present("clear") && to_terminal {
+        if let Err(e) = out.write_all(b"\x1b[2J\x1b[H") {
+            err_and_exit!(verbosity, EXIT_OUTPUT, e)
+        }
+    }
+
+    // a failed input is reported and skipped. the exit status is for the last failure
+    let mut status = 0;
+    let mut drawn = 0;
+    // images drawn in a montage are collected and laid out at the end
+    let mut tiles = Vec::new();
+
+    for input in inputs {
+        let start = Instant::now();
+        // each image in a contact sheet is captioned with its file name
+        let captioned;
+        let options = match contact_sheet {
+            Some(_) => {
+                let name = std::path::Path::new(input).file_name().map(|name| name.to_string_lossy().into_owned());
+                captioned = RenderOptions { title: name, ..options.clone() };
+                &captioned
+            }
+            None => &options
+        };
+
+        // only still images drawn in a box of cells are cached since the rest could be bigger than the file
+        let cache = cache.as_ref().filter(|_| {
+            !animate && thumbnail.is_none() && palette.is_none() && matches!(options.size, Size::Cells(..))
+        });
+        let decoding = (auto_orient, color_managed, matches.is_present("stream"));
+
+        let mut size = (0, 0);
+        let mut drawing = start;
+        // how long each step took for --time
+        let (mut decoded, mut resized, mut drawn_in) = (Duration::default(), Duration::default(), Duration::default());
+        let cached = cache.and_then(|cache| cache.get(input, options, decoding));
+        let from_cache = cached.is_some();
+        let rendered: Result<Vec<_>, String> = match cached {
+            // images drawn the same way before skip decoding and resizing
+            Some(prepared) => {
+                let mut buffer = Vec::new();
+                let drawn = render_prepared(&prepared, options, &mut buffer);
+                drawn_in = start.elapsed();
+                drawn.map(|drawn| {
+                    size = drawn;
+                    vec![(buffer, Duration::from_millis(0))]
+                })
+            }
+            None => {
+                let frames = if animate {
+                    load_frames(input, timeout, auto_orient, color_managed)
+                } else {
+                    load(input).map(|img| vec![(img, Duration::from_millis(0))])
+                };
+
+                let frames = match frames {
+                    Ok(frames) => frames,
+                    // other files in a contact sheet's directory aren't a failure
+                    Err(e) if contact_sheet.is_some() => {
+                        report!(verbosity, "{}: skipped: {}", input, e);
+                        continue;
+                    }
+                    Err(e) => {
+                        report!(verbosity, "{}: {}", input, e);
+                        status = EXIT_INPUT;
+                        continue;
+                    }
+                };
+                decoded = start.elapsed();
+
+                if color_managed && unconverted_profile(input) {
+                    report!(verbosity, "{}: {}", input, UNCONVERTED_PROFILE);
+                }
+
+                // a thumbnail that can't be saved shouldn't stop the render
+                if let Some(thumbnail) = thumbnail {
+                    let saved = resize_image(&frames[0].0, options)
+                        .and_then(|img| save_image(&img, thumbnail, image::ImageFormat::Png, None));
+                    if let Err(e) = saved {
+                        report!(verbosity, "{}: {}", thumbnail, e);
+                        status = EXIT_OUTPUT;
+                    }
+                }
+
+                if verbosity == Verbosity::Verbose {
+                    let (width, height) = frames[0].0.dimensions();
+                    let count = if frames.len() > 1 { format!(" {} frames of", frames.len()) } else { String::new() };
+                    eprintln!("{}: decoded{} {}x{} in {}ms", input, count, width, height, start.elapsed().as_millis());
+                }
+
+                if options.trim && trim_bounds(&frames[0].0, options.trim_color).is_none() {
+                    report!(verbosity, "{}: the whole image would be trimmed so it's drawn untrimmed", input);
+                }
+
+                if let Some(count) = palette {
+                    if drawn > 0 {
+                        if let Err(e) = out.write_all(b"\n") {
+                            err_and_exit!(verbosity, EXIT_OUTPUT, e)
+                        }
+                    }
+                    drawn += 1;
+
+                    if let Err(e) = render_palette(&frames[0].0, count, options, &mut out) {
+                        report!(verbosity, "{}: {}", input, e);
+                        status = EXIT_ERROR;
+                    }
+                    continue;
+                }
+
+                drawing = Instant::now();
+                frames.iter().map(|(img, delay)| {
+                    let resizing = Instant::now();
+                    let prepared = prepare_image(img, options)?;
+                    resized += resizing.elapsed();
+
+                    // a cache that can't be written to isn't worth stopping for
+                    if let Some(cache) = cache {
+                        let _ = cache.put(input, options, decoding, &prepared);
+                    }
+
+                    let mut buffer = Vec::new();
+                    let rendering = Instant::now();
+                    let drawn = render_prepared(&prepared, options, &mut buffer);
+                    drawn_in += rendering.elapsed();
+                    drawn.map(|drawn| {
+                        size = drawn;
+                        (buffer, *delay)
+                    })
+                }).collect()
+            }
+        };
+
+        let rendered = match rendered {
+            Ok(rendered) => rendered,
+            Err(e) => {
+                report!(verbosity, "{}: {}", input, e);
+                status = EXIT_ERROR;
+                continue;
+            }
+        };
+
+        if verbosity == Verbosity::Verbose {
+            eprintln!("{}: resized to {}x{} and drawn in {}ms", input, size.0, size.1, drawing.elapsed().as_millis());
+        }
+
+        if matches.is_present("time") {
+            eprintln!("{}: {}", input, timings(decoded, resized, drawn_in, from_cache));
+        }
+
+        if montage_columns.is_some() {
+            tiles.extend(rendered.into_iter().next().map(|(buffer, _)| buffer));
+            continue;
+        }
+
+        // leave a blank line between images
+        if drawn > 0 {
+            if let Err(e) = out.write_all(b"\n") {
+                err_and_exit!(verbosity, EXIT_OUTPUT, e)
+            }
+        }
+        drawn += 1;
+
+        let link = link_target(input).filter(|_| matches.is_present("link") && to_terminal);
+        if let Some(link) = &link {
+            if let Err(e) = out.write_all(link_start(link).as_bytes()) {
+                err_and_exit!(verbosity, EXIT_OUTPUT, e)
+            }
+        }
+
+        let stopped = if rendered.len() == 1 {
+            if let Err(e) = out.write_all(&rendered[0].0).and_then(|_| out.flush()) {
+                err_and_exit!(verbosity, EXIT_OUTPUT, e)
+            }
+            false
+        } else {
+            match play(&rendered, loops, to_terminal, &mut out) {
+                Ok(stopped) => stopped,
+                Err(e) => err_and_exit!(verbosity, EXIT_OUTPUT, e)
+            }
+        };
+
+        if link.is_some() {
+            if let Err(e) = out.write_all(LINK_END).and_then(|_| out.flush()) {
+                err_and_exit!(verbosity, EXIT_OUTPUT, e)
+            }
+        }
+
+        // ctrl-c stops everything instead of skipping to the next input
+        if stopped {
+            break;
+        }
+    }
+
+    if let Some(columns) = montage_columns {
+        if let Err(e) = out.write_all(&montage(&tiles, columns)).and_then(|_| out.flush()) {
+            err_and_exit!(verbosity, EXIT_OUTPUT, e)
+        }
+    }
+
+    if status != 0 {
+        std::process::exit(status);
+    }
 }
 
-fn write_image(img: image::DynamicImage, out: &mut impl Write) {
-    // image is read left to right, top to bottom so storing y works. Find a better way?
-    let mut last_y = 0;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percent_sizes() {
+        assert_eq!(get_size("50%"), Some(Size::Percent(50)));
+        assert!(validate_size("50%".to_string()).is_ok());
+        assert!(validate_size("0%".to_string()).is_err());
+        assert!(validate_size("4294967296%".to_string()).is_err());
+        assert!(validate_size("50".to_string()).is_err());
+    }
+
+    #[test]
+    fn zero_sizes() {
+        for size in &["0x10", "10x0", "0x0"] {
+            assert!(validate_size(size.to_string()).is_err(), "{}", size);
+        }
+        assert!(validate_size("10x10".to_string()).is_ok());
+        assert!(validate_size("10X10".to_string()).is_ok());
+    }
+
+    #[test]
+    fn charsets() {
+        assert!(validate_charset(" .#".to_string()).is_ok());
+        assert!(validate_charset(String::new()).is_err());
+        assert!(validate_charset("#".to_string()).is_err());
+        assert!(validate_charset(" \u{6f22}".to_string()).is_err());
+        assert!(validate_charset(" \u{301}".to_string()).is_err());
+    }
+
+    #[test]
+    fn legacy_outputs() {
+        assert!(legacy_output("/nonexistent/out.txt"));
+        assert!(!legacy_output("Cargo.toml"));
+        assert!(!legacy_output("-"));
+        assert!(!legacy_output("https://example.com/cat.png"));
+    }
+
+    #[test]
+    fn default_sizes() {
+        assert_eq!(render_size(None, false, false, false), Size::Original);
+        assert_eq!(render_size(None, true, false, true), Size::Original);
+        assert_eq!(render_size(None, false, true, false), Size::Cells(20, 10));
+        assert!(matches!(render_size(None, true, false, false), Size::Cells(..)));
 
-    for pixel in img.pixels() {
-        if pixel.1 != last_y {
-            last_y = pixel.1;
-            out.write_all(b"\n").unwrap();
+        // a size that's given is used even when it's the default
+        assert_eq!(render_size(Some("10x5"), false, true, false), Size::Cells(10, 5));
+        assert!(matches!(render_size(Some("term"), false, false, false), Size::Cells(..)));
+    }
+
+    #[test]
+    fn timing_phases() {
+        let line = timings(Duration::from_millis(3), Duration::from_micros(1500), Duration::from_millis(1), false);
+        assert_eq!(line, "decode 3.0ms, resize 1.5ms, render 1.0ms");
+        assert!(timings(Duration::default(), Duration::default(), Duration::default(), true).ends_with("(from the cache)"));
+    }
+
+    #[test]
+    fn subcommands() {
+        // the subcommand that runs and the inputs it's given
+        let parse = |line: &str| {
+            let matches = app("", "").get_matches_from(args(line.split(' ').map(OsString::from).collect()));
+            let (name, matches) = matches.subcommand();
+            (name.to_string(), matches.unwrap().values_of("input").unwrap().collect::<Vec<_>>().join(" "))
+        };
+        let parsed = |name: &str, inputs: &str| (name.to_string(), inputs.to_string());
+
+        assert_eq!(parse("image_render info a.png b.png"), parsed("info", "a.png b.png"));
+        assert_eq!(parse("image_render convert a.png b.jpg"), parsed("convert", "a.png"));
+        assert_eq!(parse("image_render render a.png"), parsed("render", "a.png"));
+        // anything that isn't a subcommand is rendered
+        assert_eq!(parse("image_render a.png"), parsed("render", "a.png"));
+        assert_eq!(parse("image_render -m ascii a.png"), parsed("render", "a.png"));
+    }
+
+    #[test]
+    fn batches() {
+        let dir = std::env::temp_dir().join(format!("image_render_batch_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("in")).unwrap();
+        for name in ["a", "b", "c"] {
+            image::RgbImage::from_pixel(40, 40, image::Rgb([255, 0, 0])).save(dir.join("in").join(format!("{}.png", name))).unwrap();
         }
+        // not an image so it's skipped
+        std::fs::write(dir.join("in").join("notes.txt"), "").unwrap();
 
-        let color = (pixel.2).0;
+        let (input, out) = (dir.join("in"), dir.join("out"));
+        let line = format!("image_render convert --batch {} --out {} --size 10x10", input.display(), out.display());
+        let matches = app("", "").get_matches_from(args(line.split(' ').map(OsString::from).collect()));
+        let matches = matches.subcommand_matches("convert").unwrap();
+        let options = RenderOptions { size: Size::Cells(10, 10), ..RenderOptions::default() };
+        let batch = convert_batch(matches, input.to_str().unwrap(), &options, None);
 
-        // ANSI true color (8 bit RGB) for background: ESC[48;2;R;G;Bm
-        out.write_all(format!("\x1b[48;2;{};{};{}m \x1b[0m", color[0], color[1], color[2]).as_bytes()).unwrap();
+        assert_eq!((batch.resized, batch.skipped, batch.status), (3, 1, 0));
+        for name in ["a", "b", "c"] {
+            assert_eq!(image::open(out.join(format!("{}.png", name))).unwrap().dimensions(), (10, 10));
+        }
+        let _ = std::fs::remove_dir_all(&dir);
     }
 
-    out.write_all(b"\n").unwrap();
-}
\ No newline at end of file
+    #[test]
+    fn links() {
+        assert_eq!(link_target("https://example.com/a.png").as_deref(), Some("https://example.com/a.png"));
+        assert_eq!(link_target("-"), None);
+
+        // files link to their absolute path with anything unusual percent-encoded
+        let dir = std::env::temp_dir().join(format!("image_render_link_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("a cat.png");
+        std::fs::write(&file, "").unwrap();
+        let target = link_target(file.to_str().unwrap()).unwrap();
+        assert!(target.starts_with("file:///") && target.ends_with("/a%20cat.png"), "{}", target);
+        let _ = std::fs::remove_dir_all(&dir);
+
+        // ESC ] 8 ; params ; URI then BEL, and an empty URI to close
+        assert_eq!(link_start("https://example.com"), "\x1b]8;;https://example.com\x07");
+        assert_eq!(LINK_END, b"\x1b]8;;\x07");
+    }
+}
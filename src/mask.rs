@@ -0,0 +1,89 @@
+use crate::DynamicImage;
+
+/// Names of the masks accepted by `get_mask`
+pub static MASKS: [&str; 2] = [
+    "circle",
+    "rounded"
+];
+
+/// Shape outside of which an image is made transparent
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Mask {
+    /// The largest circle that fits in the middle of the image
+    Circle,
+    /// The whole image with its corners rounded
+    Rounded
+}
+
+pub fn get_mask(mask: &str) -> Option<Mask> {
+    match mask {
+        "circle" => Some(Mask::Circle),
+        "rounded" => Some(Mask::Rounded),
+        _ => None
+    }
+}
+
+// rounded corners without a radius are rounded by this fraction of the shorter side
+static DEFAULT_RADIUS: u32 = 8;
+
+// scales the alpha of every pixel by how much of it is inside the mask so edges are smooth. radius is how many
+// pixels the corners of a rounded mask are rounded by
+pub(crate) fn mask(img: &DynamicImage, mask: Mask, radius: Option<u32>) -> DynamicImage {
+    let mut img = img.to_rgba8();
+    let (width, height) = (img.width() as f32, img.height() as f32);
+    let shorter = width.min(height);
+
+    let radius = match mask {
+        Mask::Circle => shorter / 2.0,
+        Mask::Rounded => radius.map_or(shorter / DEFAULT_RADIUS as f32, |radius| radius as f32).min(shorter / 2.0)
+    };
+    // a radius of half a pixel leaves every pixel whole
+    let radius = radius.max(0.5);
+
+    for (x, y, pixel) in img.enumerate_pixels_mut() {
+        let (x, y) = (x as f32 + 0.5, y as f32 + 0.5);
+        // the closest point that's far enough from every edge to be the center of a circle of radius. only pixels
+        // past it are outside the circle of a rounded corner
+        let (cx, cy) = match mask {
+            Mask::Circle => (width / 2.0, height / 2.0),
+            Mask::Rounded => (x.max(radius).min(width - radius), y.max(radius).min(height - radius))
+        };
+        let distance = ((x - cx).powi(2) + (y - cy).powi(2)).sqrt();
+        // pixels straddling the edge are partly inside
+        let inside = (radius - distance + 0.5).clamp(0.0, 1.0);
+        pixel[3] = (f32::from(pixel[3]) * inside).round() as u8;
+    }
+    DynamicImage::ImageRgba8(img)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the alpha of each pixel after masking a white square
+    fn alpha(shape: Mask, radius: Option<u32>) -> image::GrayImage {
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(20, 20, image::Rgba([255, 255, 255, 255])));
+        let masked = mask(&img, shape, radius).to_rgba8();
+        image::GrayImage::from_fn(20, 20, |x, y| image::Luma([masked.get_pixel(x, y)[3]]))
+    }
+
+    #[test]
+    fn circles() {
+        let alpha = alpha(Mask::Circle, None);
+        for (x, y) in [(0, 0), (19, 0), (0, 19), (19, 19)] {
+            assert_eq!(alpha.get_pixel(x, y)[0], 0);
+        }
+        assert_eq!(alpha.get_pixel(10, 10)[0], 255);
+        // the middle of each edge touches the circle so it's mostly inside
+        assert!(alpha.get_pixel(0, 10)[0] > 200);
+    }
+
+    #[test]
+    fn rounded_corners() {
+        let alpha = alpha(Mask::Rounded, Some(5));
+        assert_eq!(alpha.get_pixel(0, 0)[0], 0);
+        // only the corners are cut
+        assert_eq!(alpha.get_pixel(5, 0)[0], 255);
+        assert_eq!(alpha.get_pixel(10, 10)[0], 255);
+    }
+}
@@ -0,0 +1,145 @@
+use std::path::Path;
+
+use image::codecs::pnm::{PNMSubtype, PnmEncoder, SampleEncoding};
+use image::{GenericImageView, ImageFormat};
+
+use crate::DynamicImage;
+
+// PNM files can only hold the colors their extension names so the image is converted to them first. files without
+// one of the extensions are PPM
+fn encode_pnm(img: &DynamicImage, path: &Path, out: &mut Vec<u8>) -> Result<(), String> {
+    let extension = path.extension().and_then(|extension| extension.to_str()).map(str::to_ascii_lowercase);
+    let (img, subtype) = match extension.as_deref() {
+        // PBM pixels are either black or white
+        Some("pbm") => {
+            let mut img = img.to_luma8();
+            img.pixels_mut().for_each(|pixel| pixel[0] = if pixel[0] >= 128 { 255 } else { 0 });
+            (DynamicImage::ImageLuma8(img), Some(PNMSubtype::Bitmap(SampleEncoding::Binary)))
+        }
+        Some("pgm") => (DynamicImage::ImageLuma8(img.to_luma8()), Some(PNMSubtype::Graymap(SampleEncoding::Binary))),
+        // PAM keeps the color type of the image
+        Some("pam") => (img.clone(), None),
+        _ => (DynamicImage::ImageRgb8(img.to_rgb8()), Some(PNMSubtype::Pixmap(SampleEncoding::Binary)))
+    };
+
+    let mut encoder = match subtype {
+        Some(subtype) => PnmEncoder::new(out).with_subtype(subtype),
+        None => PnmEncoder::new(out)
+    };
+    encoder.encode(img.as_bytes(), img.width(), img.height(), img.color()).map_err(|e| e.to_string())
+}
+
+/// Saves an image to path in format. quality is from 1 to 100 and only used by JPEG
+pub fn save_image(img: &DynamicImage, path: impl AsRef<Path>, format: ImageFormat, quality: Option<u8>)
+    -> Result<(), String> {
+    let path = path.as_ref();
+    let mut encoded = Vec::new();
+    match format {
+        ImageFormat::Jpeg => {
+            let mut encoder = match quality {
+                Some(quality) => image::codecs::jpeg::JpegEncoder::new_with_quality(&mut encoded, quality),
+                None => image::codecs::jpeg::JpegEncoder::new(&mut encoded)
+            };
+            encoder.encode_image(img).map_err(|e| e.to_string())?;
+        }
+        ImageFormat::Pnm => encode_pnm(img, path, &mut encoded)?,
+        format => return img.save_with_format(path, format).map_err(|e| e.to_string())
+    }
+    std::fs::write(path, encoded).map_err(|e| e.to_string())
+}
+
+/// Saves each frame in dir as frame_0001.png and so on, making dir if it doesn't exist. Errors name the file
+pub fn save_frames(frames: &[DynamicImage], dir: impl AsRef<Path>) -> Result<(), String> {
+    let dir = dir.as_ref();
+    std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+
+    // every index is padded to the same width so the files sort in order
+    let digits = frames.len().to_string().len().max(4);
+    for (i, frame) in frames.iter().enumerate() {
+        let path = dir.join(format!("frame_{:0width$}.png", i + 1, width = digits));
+        save_image(frame, &path, ImageFormat::Png, None).map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{resize_image, RenderOptions, ResizeMode, Size};
+
+    // a directory of its own for a test's files
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("image_render_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn saved_pngs_decode() {
+        let path = temp_dir("saved_png").join("out.png");
+        let img = DynamicImage::ImageRgb8(image::RgbImage::from_pixel(40, 20, image::Rgb([255, 0, 0])));
+        let resized = resize_image(&img, &RenderOptions { size: Size::Cells(10, 10), ..RenderOptions::default() }).unwrap();
+        save_image(&resized, &path, ImageFormat::from_path(&path).unwrap(), None).unwrap();
+
+        let saved = image::open(&path).unwrap();
+        assert_eq!(saved.dimensions(), (10, 5));
+        assert_eq!(saved.to_rgb8().get_pixel(0, 0).0, [255, 0, 0]);
+    }
+
+    #[test]
+    fn gif_frames_split_into_files() {
+        let dir = temp_dir("split_frames");
+        let gif = dir.join("in.gif");
+        {
+            let mut encoder = image::codecs::gif::GifEncoder::new(std::fs::File::create(&gif).unwrap());
+            for shade in [0, 128, 255] {
+                let frame = image::RgbaImage::from_pixel(4, 4, image::Rgba([shade, shade, shade, 255]));
+                encoder.encode_frame(image::Frame::new(frame)).unwrap();
+            }
+        }
+
+        let frames = crate::load_frames(gif.to_str().unwrap(), std::time::Duration::from_secs(1), false, false).unwrap();
+        let frames: Vec<_> = frames.into_iter().map(|(frame, _)| frame).collect();
+        save_frames(&frames, dir.join("frames")).unwrap();
+
+        let mut files: Vec<_> = std::fs::read_dir(dir.join("frames")).unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.sort();
+        assert_eq!(files, ["frame_0001.png", "frame_0002.png", "frame_0003.png"]);
+        let last = image::open(dir.join("frames").join("frame_0003.png")).unwrap().to_rgba8();
+        assert_eq!(last.get_pixel(0, 0), &image::Rgba([255, 255, 255, 255]));
+    }
+
+    #[test]
+    fn converted_jpegs_decode() {
+        let dir = temp_dir("converted_jpeg");
+        let png = dir.join("in.png");
+        DynamicImage::ImageRgb8(image::RgbImage::from_pixel(120, 80, image::Rgb([0, 128, 255]))).save(&png).unwrap();
+
+        // like convert in.png out.jpg --size 50x50 --resize-mode stretch --quality 90
+        let img = crate::load_image(png.to_str().unwrap(), std::time::Duration::from_secs(1), true, false).unwrap();
+        let options = RenderOptions { size: Size::Cells(50, 50), resize_mode: ResizeMode::Stretch, ..RenderOptions::default() };
+        let jpeg = dir.join("out.jpg");
+        save_image(&resize_image(&img, &options).unwrap(), &jpeg, ImageFormat::Jpeg, Some(90)).unwrap();
+
+        let bytes = std::fs::read(&jpeg).unwrap();
+        assert_eq!(image::guess_format(&bytes).unwrap(), ImageFormat::Jpeg);
+        let saved = image::load_from_memory(&bytes).unwrap().to_rgb8();
+        assert_eq!(saved.dimensions(), (50, 50));
+        let pixel = saved.get_pixel(25, 25);
+        assert!(pixel[0] < 10 && (118..138).contains(&pixel[1]) && pixel[2] > 245, "{:?}", pixel);
+    }
+
+    #[test]
+    fn ppms_round_trip() {
+        let path = temp_dir("ppm").join("out.ppm");
+        let img = DynamicImage::ImageRgb8(image::RgbImage::from_fn(5, 3, |x, y| image::Rgb([x as u8 * 50, y as u8 * 100, 7])));
+        save_image(&img, &path, ImageFormat::from_path(&path).unwrap(), None).unwrap();
+
+        let bytes = std::fs::read(&path).unwrap();
+        assert!(bytes.starts_with(b"P6"));
+        assert_eq!(image::load_from_memory(&bytes).unwrap(), img);
+    }
+}
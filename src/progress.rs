@@ -0,0 +1,76 @@
+use std::io::{stderr, Write};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+
+// width of the bar in cells, not counting the brackets and percentage
+static BAR_WIDTH: usize = 30;
+
+// the cells of the bar that are filled and the bar itself when done of rows lines have been drawn
+fn bar(done: u32, rows: u32) -> (usize, String) {
+    let done = done.min(rows);
+    let filled = (done as usize * BAR_WIDTH) / rows as usize;
+    let percent = done * 100 / rows;
+    (filled, format!("\r[{}{}] {:3}%", "#".repeat(filled), " ".repeat(BAR_WIDTH - filled), percent))
+}
+
+// draws a bar on stderr out of the lines expected as each one is drawn. lines can be drawn on any thread, so with
+// rayon they're counted as they're finished rather than as they're written out in order
+pub(crate) struct Progress {
+    rows: u32,
+    done: AtomicU32,
+    // how many cells of the bar are showing
+    shown: Mutex<Option<usize>>
+}
+
+impl Progress {
+    pub(crate) fn new(rows: u32) -> Self {
+        Progress { rows: rows.max(1), done: AtomicU32::new(0), shown: Mutex::new(None) }
+    }
+
+    // counts another line as drawn
+    pub(crate) fn line(&self) {
+        let done = self.done.fetch_add(1, Ordering::Relaxed) + 1;
+        let (filled, bar) = bar(done, self.rows);
+
+        // only redraw when the bar changes so stderr isn't flooded
+        let mut shown = self.shown.lock().unwrap();
+        if shown.is_some_and(|shown| shown >= filled) {
+            return;
+        }
+        *shown = Some(filled);
+        let _ = stderr().write_all(bar.as_bytes());
+    }
+
+    // erases the bar from the line it was drawn on
+    pub(crate) fn finish(self) {
+        if self.shown.into_inner().unwrap().is_some() {
+            let _ = stderr().write_all(b"\r\x1b[K");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bars() {
+        assert_eq!(bar(0, 10), (0, format!("\r[{}]   0%", " ".repeat(30))));
+        assert_eq!(bar(5, 10), (15, format!("\r[{}{}]  50%", "#".repeat(15), " ".repeat(15))));
+        // lines past the end don't overflow the bar
+        assert_eq!(bar(12, 10), bar(10, 10));
+        assert_eq!(bar(10, 10).0, BAR_WIDTH);
+    }
+
+    #[test]
+    fn lines_from_every_thread_count() {
+        let progress = Progress::new(100);
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| (0..25).for_each(|_| progress.line()));
+            }
+        });
+        assert_eq!(progress.done.load(Ordering::Relaxed), 100);
+        assert_eq!(*progress.shown.lock().unwrap(), Some(BAR_WIDTH));
+    }
+}
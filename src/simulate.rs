@@ -0,0 +1,93 @@
+use crate::color::{from_linear, to_linear};
+use crate::DynamicImage;
+
+/// Names of the color vision deficiencies accepted by `get_deficiency`
+pub static DEFICIENCIES: [&str; 3] = [
+    "protanopia",
+    "deuteranopia",
+    "tritanopia"
+];
+
+/// Color vision deficiency an image can be shown as seen with
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Deficiency {
+    /// No red cones
+    Protanopia,
+    /// No green cones
+    Deuteranopia,
+    /// No blue cones
+    Tritanopia
+}
+
+pub fn get_deficiency(deficiency: &str) -> Option<Deficiency> {
+    match deficiency {
+        "protanopia" => Some(Deficiency::Protanopia),
+        "deuteranopia" => Some(Deficiency::Deuteranopia),
+        "tritanopia" => Some(Deficiency::Tritanopia),
+        _ => None
+    }
+}
+
+// matrices for full severity from Machado, Oliveira, and Fernandes 2009. they work on linear RGB
+fn matrix(deficiency: Deficiency) -> [[f32; 3]; 3] {
+    match deficiency {
+        Deficiency::Protanopia => [
+            [0.152_286, 1.052_583, -0.204_868],
+            [0.114_503, 0.786_281, 0.099_216],
+            [-0.003_882, -0.048_116, 1.051_998]
+        ],
+        Deficiency::Deuteranopia => [
+            [0.367_322, 0.860_646, -0.227_968],
+            [0.280_085, 0.672_501, 0.047_413],
+            [-0.011_820, 0.042_940, 0.968_881]
+        ],
+        Deficiency::Tritanopia => [
+            [1.255_528, -0.076_749, -0.178_779],
+            [-0.078_411, 0.930_809, 0.147_602],
+            [0.004_733, 0.691_367, 0.303_900]
+        ]
+    }
+}
+
+// the colors of the image as someone with the deficiency sees them
+pub(crate) fn simulate(img: &DynamicImage, deficiency: Deficiency) -> DynamicImage {
+    let matrix = matrix(deficiency);
+    let linear: Vec<f32> = (0..=255).map(|value| to_linear(value as f32 / 255.0)).collect();
+
+    let mut img = img.to_rgba8();
+    for pixel in img.pixels_mut() {
+        let rgb = [linear[usize::from(pixel[0])], linear[usize::from(pixel[1])], linear[usize::from(pixel[2])]];
+        for (i, row) in matrix.iter().enumerate() {
+            pixel[i] = (from_linear(row[0] * rgb[0] + row[1] * rgb[1] + row[2] * rgb[2]) * 255.0).round() as u8;
+        }
+    }
+    DynamicImage::ImageRgba8(img)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the color of a solid image as someone with the deficiency sees it
+    fn seen(color: [u8; 3], deficiency: Deficiency) -> [u8; 3] {
+        let img = DynamicImage::ImageRgb8(image::RgbImage::from_pixel(1, 1, image::Rgb(color)));
+        simulate(&img, deficiency).to_rgb8().get_pixel(0, 0).0
+    }
+
+    // red and green are about as strong as each other, which looks yellow
+    fn yellowish(color: [u8; 3]) -> bool {
+        let (red, green) = (u32::from(color[0]), u32::from(color[1]));
+        red >= green && green * 10 >= red * 8
+    }
+
+    #[test]
+    fn deuteranopes_confuse_red_and_green() {
+        assert!(yellowish(seen([255, 0, 0], Deficiency::Deuteranopia)));
+        assert!(yellowish(seen([0, 255, 0], Deficiency::Deuteranopia)));
+        assert_eq!(seen([255, 255, 255], Deficiency::Deuteranopia), [255, 255, 255]);
+
+        // without blue cones red and green are still told apart
+        assert!(!yellowish(seen([255, 0, 0], Deficiency::Tritanopia)));
+        assert!(!yellowish(seen([0, 255, 0], Deficiency::Tritanopia)));
+    }
+}
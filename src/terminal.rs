@@ -0,0 +1,167 @@
+use std::io::IsTerminal;
+use std::time::Duration;
+
+pub(crate) use tty::RawTty;
+
+// how long the terminal has to answer a query before the configured ratio is used
+static QUERY_TIMEOUT: Duration = Duration::from_millis(100);
+
+// reads a report like ESC [ 6 ; height ; width t. the kind is the first number, 6 for a cell's size in pixels and
+// 4 for the whole window's. a size of 0 means the terminal doesn't know
+pub(crate) fn parse_size_report(report: &[u8]) -> Option<(u32, u32, u32)> {
+    let body = std::str::from_utf8(report).ok()?.strip_prefix("\x1b[")?.strip_suffix('t')?;
+    let mut numbers = body.split(';').map(|number| number.parse::<u32>().ok());
+    match (numbers.next()?, numbers.next()?, numbers.next()?, numbers.next()) {
+        (Some(kind), Some(height), Some(width), None) if height > 0 && width > 0 => Some((kind, height, width)),
+        _ => None
+    }
+}
+
+/// Works out the height of a terminal cell divided by its width from its size in pixels. None if stdout isn't a
+/// terminal or it doesn't say how big its cells are
+pub fn detect_font_ratio() -> Option<f32> {
+    // the terminal could be asked through /dev/tty, but the output isn't going to it
+    if !std::io::stdout().is_terminal() {
+        return None;
+    }
+    let ((height, width), (rows, columns)) = tty::window_pixels().or_else(query_cell)?;
+    if height == 0 || width == 0 || rows == 0 || columns == 0 {
+        return None;
+    }
+    Some((height as f32 / rows as f32) / (width as f32 / columns as f32))
+}
+
+// asks the terminal for the size of one cell in pixels and reads up to the t that ends the answer
+fn query_cell() -> Option<((u32, u32), (u32, u32))> {
+    let mut tty = RawTty::open()?;
+    tty.write_all(b"\x1b[16t")?;
+
+    let mut report = Vec::new();
+    while report.last() != Some(&b't') {
+        if report.len() == 32 {
+            return None;
+        }
+        report.push(tty.read(QUERY_TIMEOUT)?);
+    }
+
+    match parse_size_report(&report)? {
+        (6, height, width) => Some(((height, width), (1, 1))),
+        _ => None
+    }
+}
+
+#[cfg(unix)]
+mod tty {
+    use std::fs::{File, OpenOptions};
+    use std::io::{Read, Write};
+    use std::os::unix::io::AsRawFd;
+    use std::time::Duration;
+
+    // the window's size in pixels and cells from the kernel. lots of terminals leave the pixels as 0, which means
+    // they're unknown
+    pub(super) fn window_pixels() -> Option<((u32, u32), (u32, u32))> {
+        let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+        if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } != 0 || size.ws_xpixel == 0
+            || size.ws_ypixel == 0 {
+            return None;
+        }
+        Some(((size.ws_ypixel.into(), size.ws_xpixel.into()), (size.ws_row.into(), size.ws_col.into())))
+    }
+
+    // the terminal with echo and line buffering off so keys and answers to queries can be read as they come. it's
+    // put back how it was when this is dropped. ctrl-c still sends a signal
+    pub(crate) struct RawTty {
+        tty: File,
+        original: libc::termios
+    }
+
+    impl RawTty {
+        pub(crate) fn open() -> Option<Self> {
+            let tty = OpenOptions::new().read(true).write(true).open("/dev/tty").ok()?;
+            let mut original: libc::termios = unsafe { std::mem::zeroed() };
+            if unsafe { libc::tcgetattr(tty.as_raw_fd(), &mut original) } != 0 {
+                return None;
+            }
+            let mut raw = original;
+            raw.c_lflag &= !(libc::ICANON | libc::ECHO);
+            unsafe { libc::tcsetattr(tty.as_raw_fd(), libc::TCSANOW, &raw) };
+            Some(RawTty { tty, original })
+        }
+
+        pub(crate) fn write_all(&mut self, buf: &[u8]) -> Option<()> {
+            self.tty.write_all(buf).ok()
+        }
+
+        // the next byte typed, or None if nothing comes within timeout
+        pub(crate) fn read(&mut self, timeout: Duration) -> Option<u8> {
+            let mut poll = libc::pollfd { fd: self.tty.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+            if unsafe { libc::poll(&mut poll, 1, timeout.as_millis() as i32) } <= 0 {
+                return None;
+            }
+            let mut byte = [0];
+            self.tty.read_exact(&mut byte).ok()?;
+            Some(byte[0])
+        }
+    }
+
+    impl Drop for RawTty {
+        fn drop(&mut self) {
+            unsafe { libc::tcsetattr(self.tty.as_raw_fd(), libc::TCSANOW, &self.original) };
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod tty {
+    use std::time::Duration;
+
+    pub(super) fn window_pixels() -> Option<((u32, u32), (u32, u32))> {
+        None
+    }
+
+    // there's no /dev/tty to read keys from
+    pub(crate) struct RawTty;
+
+    impl RawTty {
+        pub(crate) fn open() -> Option<Self> {
+            None
+        }
+
+        pub(crate) fn write_all(&mut self, _: &[u8]) -> Option<()> {
+            None
+        }
+
+        pub(crate) fn read(&mut self, _: Duration) -> Option<u8> {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn size_reports() {
+        assert_eq!(parse_size_report(b"\x1b[6;16;8t"), Some((6, 16, 8)));
+        assert_eq!(parse_size_report(b"\x1b[4;720;1280t"), Some((4, 720, 1280)));
+
+        // truncated
+        assert_eq!(parse_size_report(b"\x1b[6;16;8"), None);
+        assert_eq!(parse_size_report(b"\x1b[6;16t"), None);
+        assert_eq!(parse_size_report(b"6;16;8t"), None);
+
+        // garbage
+        assert_eq!(parse_size_report(b""), None);
+        assert_eq!(parse_size_report(b"\x1b[6;a;8t"), None);
+        assert_eq!(parse_size_report(b"\x1b[6;16;8;1t"), None);
+        assert_eq!(parse_size_report(b"\x1b[6;-16;8t"), None);
+        assert_eq!(parse_size_report(&[0x1b, b'[', 0xff, b't']), None);
+    }
+
+    #[test]
+    fn zero_sized_cells() {
+        assert_eq!(parse_size_report(b"\x1b[6;0;8t"), None);
+        assert_eq!(parse_size_report(b"\x1b[6;16;0t"), None);
+    }
+}
@@ -0,0 +1,115 @@
+use image::ColorType;
+
+use crate::color::{from_linear, to_linear};
+use crate::DynamicImage;
+
+/// Names of the tone maps accepted by `get_tonemap`
+pub static TONEMAPS: [&str; 2] = [
+    "linear",
+    "reinhard"
+];
+
+/// How 16 bit images are brought down to 8 bits
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Tonemap {
+    /// Stretch the darkest and brightest values in the image to black and white
+    Linear,
+    /// Compress bright values more than dark ones so highlights keep their detail
+    Reinhard
+}
+
+pub fn get_tonemap(tonemap: &str) -> Option<Tonemap> {
+    match tonemap {
+        "linear" => Some(Tonemap::Linear),
+        "reinhard" => Some(Tonemap::Reinhard),
+        _ => None
+    }
+}
+
+// middle gray that the average brightness is mapped to by reinhard
+static KEY: f32 = 0.18;
+
+// maps every channel but alpha linearly so low is 0 and high is 255
+fn stretch(img: &image::ImageBuffer<image::Rgba<u16>, Vec<u16>>) -> image::RgbaImage {
+    let values = || img.pixels().flat_map(|pixel| pixel.0[..3].to_vec());
+    let (low, high) = (values().min().unwrap_or(0), values().max().unwrap_or(u16::MAX));
+    // a solid image has nothing to stretch
+    let (low, high) = if low == high { (0, u16::MAX) } else { (low, high) };
+    let scale = 255.0 / f32::from(high - low);
+
+    image::RgbaImage::from_fn(img.width(), img.height(), |x, y| {
+        let pixel = img.get_pixel(x, y);
+        let channel = |i: usize| (f32::from(pixel[i] - low) * scale).round() as u8;
+        image::Rgba([channel(0), channel(1), channel(2), (pixel[3] >> 8) as u8])
+    })
+}
+
+// global operator from Reinhard et al. 2002. brightness is scaled so the average is middle gray then L / (1 + L)
+// squeezes it between 0 and 1
+fn reinhard(img: &image::ImageBuffer<image::Rgba<u16>, Vec<u16>>) -> image::RgbaImage {
+    let decode: Vec<f32> = (0..=u16::MAX).map(|value| to_linear(f32::from(value) / 65535.0)).collect();
+    let linear = |pixel: &image::Rgba<u16>| [decode[usize::from(pixel[0])], decode[usize::from(pixel[1])],
+                                             decode[usize::from(pixel[2])]];
+    let luminance = |rgb: [f32; 3]| 0.2126 * rgb[0] + 0.7152 * rgb[1] + 0.0722 * rgb[2];
+
+    // the log average isn't thrown off by a few very bright pixels. the small offset keeps black from being -inf
+    let count = (img.width() * img.height()).max(1) as f32;
+    let average = (img.pixels().map(|pixel| (1e-4 + luminance(linear(pixel))).ln()).sum::<f32>() / count).exp();
+
+    image::RgbaImage::from_fn(img.width(), img.height(), |x, y| {
+        let pixel = img.get_pixel(x, y);
+        let rgb = linear(pixel);
+        let before = luminance(rgb);
+        let scaled = KEY / average * before;
+        let after = scaled / (1.0 + scaled);
+        // the color keeps its hue and only its brightness changes
+        let ratio = if before > 0.0 { after / before } else { 0.0 };
+        let channel = |i: usize| (from_linear(rgb[i] * ratio) * 255.0).round() as u8;
+        image::Rgba([channel(0), channel(1), channel(2), (pixel[3] >> 8) as u8])
+    })
+}
+
+// brings a 16 bit image down to 8 bits. None if it's already 8 bits
+pub(crate) fn tonemap(img: &DynamicImage, tonemap: Tonemap) -> Option<DynamicImage> {
+    if !matches!(img.color(), ColorType::L16 | ColorType::La16 | ColorType::Rgb16 | ColorType::Rgba16) {
+        return None;
+    }
+
+    let img = img.to_rgba16();
+    Some(DynamicImage::ImageRgba8(match tonemap {
+        Tonemap::Linear => stretch(&img),
+        Tonemap::Reinhard => reinhard(&img)
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a dim 16 bit gradient that would be nearly black if its low bits were cut off
+    fn gradient() -> DynamicImage {
+        DynamicImage::ImageLuma16(image::ImageBuffer::from_fn(256, 1, |x, _| image::Luma([1000 + x as u16 * 16])))
+    }
+
+    // the gray of each pixel from left to right
+    fn grays(img: DynamicImage) -> Vec<u8> {
+        img.to_rgba8().pixels().map(|pixel| pixel[0]).collect()
+    }
+
+    #[test]
+    fn linear_stretches_to_the_full_range() {
+        let grays = grays(tonemap(&gradient(), Tonemap::Linear).unwrap());
+        assert_eq!((grays[0], grays[255]), (0, 255));
+        assert!(grays.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert!(tonemap(&DynamicImage::new_rgb8(2, 2), Tonemap::Linear).is_none());
+    }
+
+    #[test]
+    fn reinhard_keeps_the_order() {
+        let grays = grays(tonemap(&gradient(), Tonemap::Reinhard).unwrap());
+        assert!(grays.windows(2).all(|pair| pair[0] <= pair[1]));
+        // the average is brought up to middle gray without blowing out the brightest pixel
+        assert!(grays[0] > 0 && grays[255] < 255, "{:?}", (grays[0], grays[255]));
+        assert!((90..160).contains(&grays[128]), "{}", grays[128]);
+    }
+}
@@ -0,0 +1,177 @@
+use crate::DynamicImage;
+
+/// Names of the corners accepted by `get_corner`
+pub static CORNERS: [&str; 4] = [
+    "top-left",
+    "top-right",
+    "bottom-left",
+    "bottom-right"
+];
+
+/// Corner of the image something is drawn in
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight
+}
+
+pub fn get_corner(corner: &str) -> Option<Corner> {
+    match corner {
+        "top-left" => Some(Corner::TopLeft),
+        "top-right" => Some(Corner::TopRight),
+        "bottom-left" => Some(Corner::BottomLeft),
+        "bottom-right" => Some(Corner::BottomRight),
+        _ => None
+    }
+}
+
+// 3x5 pixel font built in so no font has to be installed. each row is 3 bits with the left pixel in the highest
+// bit. lowercase letters are drawn as uppercase and anything else missing is drawn as ?
+static FONT: [(char, [u8; 5]); 59] = [
+    (' ', [0b000, 0b000, 0b000, 0b000, 0b000]),
+    ('A', [0b010, 0b101, 0b111, 0b101, 0b101]),
+    ('B', [0b110, 0b101, 0b110, 0b101, 0b110]),
+    ('C', [0b011, 0b100, 0b100, 0b100, 0b011]),
+    ('D', [0b110, 0b101, 0b101, 0b101, 0b110]),
+    ('E', [0b111, 0b100, 0b110, 0b100, 0b111]),
+    ('F', [0b111, 0b100, 0b110, 0b100, 0b100]),
+    ('G', [0b011, 0b100, 0b101, 0b101, 0b011]),
+    ('H', [0b101, 0b101, 0b111, 0b101, 0b101]),
+    ('I', [0b111, 0b010, 0b010, 0b010, 0b111]),
+    ('J', [0b001, 0b001, 0b001, 0b101, 0b010]),
+    ('K', [0b101, 0b101, 0b110, 0b101, 0b101]),
+    ('L', [0b100, 0b100, 0b100, 0b100, 0b111]),
+    ('M', [0b101, 0b111, 0b101, 0b101, 0b101]),
+    ('N', [0b110, 0b101, 0b101, 0b101, 0b101]),
+    ('O', [0b010, 0b101, 0b101, 0b101, 0b010]),
+    ('P', [0b110, 0b101, 0b110, 0b100, 0b100]),
+    ('Q', [0b010, 0b101, 0b101, 0b110, 0b011]),
+    ('R', [0b110, 0b101, 0b110, 0b101, 0b101]),
+    ('S', [0b011, 0b100, 0b010, 0b001, 0b110]),
+    ('T', [0b111, 0b010, 0b010, 0b010, 0b010]),
+    ('U', [0b101, 0b101, 0b101, 0b101, 0b111]),
+    ('V', [0b101, 0b101, 0b101, 0b101, 0b010]),
+    ('W', [0b101, 0b101, 0b111, 0b111, 0b101]),
+    ('X', [0b101, 0b101, 0b010, 0b101, 0b101]),
+    ('Y', [0b101, 0b101, 0b010, 0b010, 0b010]),
+    ('Z', [0b111, 0b001, 0b010, 0b100, 0b111]),
+    ('0', [0b111, 0b101, 0b101, 0b101, 0b111]),
+    ('1', [0b010, 0b110, 0b010, 0b010, 0b111]),
+    ('2', [0b110, 0b001, 0b010, 0b100, 0b111]),
+    ('3', [0b110, 0b001, 0b010, 0b001, 0b110]),
+    ('4', [0b101, 0b101, 0b111, 0b001, 0b001]),
+    ('5', [0b111, 0b100, 0b110, 0b001, 0b110]),
+    ('6', [0b011, 0b100, 0b111, 0b101, 0b111]),
+    ('7', [0b111, 0b001, 0b010, 0b010, 0b010]),
+    ('8', [0b111, 0b101, 0b111, 0b101, 0b111]),
+    ('9', [0b111, 0b101, 0b111, 0b001, 0b110]),
+    ('.', [0b000, 0b000, 0b000, 0b000, 0b010]),
+    (',', [0b000, 0b000, 0b000, 0b010, 0b100]),
+    (':', [0b000, 0b010, 0b000, 0b010, 0b000]),
+    (';', [0b000, 0b010, 0b000, 0b010, 0b100]),
+    ('!', [0b010, 0b010, 0b010, 0b000, 0b010]),
+    ('?', [0b110, 0b001, 0b010, 0b000, 0b010]),
+    ('-', [0b000, 0b000, 0b111, 0b000, 0b000]),
+    ('_', [0b000, 0b000, 0b000, 0b000, 0b111]),
+    ('\'', [0b010, 0b010, 0b000, 0b000, 0b000]),
+    ('"', [0b101, 0b101, 0b000, 0b000, 0b000]),
+    ('(', [0b001, 0b010, 0b010, 0b010, 0b001]),
+    (')', [0b100, 0b010, 0b010, 0b010, 0b100]),
+    ('/', [0b001, 0b001, 0b010, 0b100, 0b100]),
+    ('+', [0b000, 0b010, 0b111, 0b010, 0b000]),
+    ('=', [0b000, 0b111, 0b000, 0b111, 0b000]),
+    ('#', [0b101, 0b111, 0b101, 0b111, 0b101]),
+    ('%', [0b101, 0b001, 0b010, 0b100, 0b101]),
+    ('&', [0b010, 0b101, 0b010, 0b101, 0b011]),
+    ('*', [0b101, 0b010, 0b101, 0b000, 0b000]),
+    ('@', [0b111, 0b101, 0b111, 0b100, 0b111]),
+    ('<', [0b001, 0b010, 0b100, 0b010, 0b001]),
+    ('>', [0b100, 0b010, 0b001, 0b010, 0b100])
+];
+
+fn glyph(c: char) -> [u8; 5] {
+    let c = c.to_ascii_uppercase();
+    let find = |c: char| FONT.iter().find(|&&(glyph, _)| glyph == c).map(|&(_, rows)| rows);
+    find(c).or_else(|| find('?')).unwrap()
+}
+
+// how opaque the text is so the image shows through
+static OPACITY: f32 = 0.6;
+
+// draws text in a corner of the image. the font is scaled up with the image so the text is about a tenth as tall in
+// big images and one pixel per dot in small ones
+pub(crate) fn watermark(img: &DynamicImage, text: &str, corner: Corner, color: [u8; 3]) -> DynamicImage {
+    let mut img = img.to_rgba8();
+    let (width, height) = img.dimensions();
+
+    let scale = (height / 50).max(1);
+    let count = text.chars().count() as u32;
+    // one dot between letters and around the text
+    let text_width = (count * 4).saturating_sub(1) * scale;
+    let text_height = 5 * scale;
+
+    let left = match corner {
+        Corner::TopLeft | Corner::BottomLeft => scale,
+        Corner::TopRight | Corner::BottomRight => width.saturating_sub(text_width + scale)
+    };
+    let top = match corner {
+        Corner::TopLeft | Corner::TopRight => scale,
+        Corner::BottomLeft | Corner::BottomRight => height.saturating_sub(text_height + scale)
+    };
+
+    for (i, c) in text.chars().enumerate() {
+        let rows = glyph(c);
+        for y in 0..text_height {
+            for x in 0..3 * scale {
+                if rows[(y / scale) as usize] & (0b100 >> (x / scale)) == 0 {
+                    continue;
+                }
+
+                let (px, py) = (left + i as u32 * 4 * scale + x, top + y);
+                // text that doesn't fit is cut off
+                if px >= width || py >= height {
+                    continue;
+                }
+
+                let pixel = img.get_pixel_mut(px, py);
+                for channel in 0..3 {
+                    let blended = f32::from(color[channel]) * OPACITY + f32::from(pixel[channel]) * (1.0 - OPACITY);
+                    pixel[channel] = blended.round() as u8;
+                }
+                pixel[3] = pixel[3].max((OPACITY * 255.0).round() as u8);
+            }
+        }
+    }
+    DynamicImage::ImageRgba8(img)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the bounds of the pixels the watermark changed
+    fn changed(corner: Corner) -> (u32, u32, u32, u32) {
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(100, 100, image::Rgba([0, 0, 0, 255])));
+        let marked = watermark(&img, "HI", corner, [255, 255, 255]).to_rgba8();
+        let changed: Vec<_> = marked.enumerate_pixels().filter(|(_, _, pixel)| pixel[0] > 0).map(|(x, y, _)| (x, y)).collect();
+        assert!(!changed.is_empty());
+        let xs = changed.iter().map(|&(x, _)| x);
+        let ys = changed.iter().map(|&(_, y)| y);
+        (xs.clone().min().unwrap(), ys.clone().min().unwrap(), xs.max().unwrap(), ys.max().unwrap())
+    }
+
+    #[test]
+    fn corners() {
+        // text is 7 dots wide and 5 tall, a dot is 2 pixels, and there's a dot around it
+        let (left, top, right, bottom) = changed(Corner::TopLeft);
+        assert!(left >= 2 && top >= 2 && right < 50 && bottom < 50);
+        let (left, top, right, bottom) = changed(Corner::TopRight);
+        assert!(left >= 50 && top >= 2 && right <= 97 && bottom < 50);
+        let (left, top, right, bottom) = changed(Corner::BottomLeft);
+        assert!(left >= 2 && top >= 50 && right < 50 && bottom <= 97);
+        let (left, top, right, bottom) = changed(Corner::BottomRight);
+        assert!(left >= 50 && top >= 50 && right <= 97 && bottom <= 97);
+    }
+}
@@ -0,0 +1,54 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use image::GenericImageView;
+use image_render::{load_image, load_image_streamed};
+
+// counts the bytes allocated so the most in use at once can be checked
+struct Counting;
+
+static IN_USE: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let in_use = IN_USE.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+        PEAK.fetch_max(in_use, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        IN_USE.fetch_sub(layout.size(), Ordering::SeqCst);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+// the most bytes in use at once while load runs, on top of what was in use before it
+fn peak<T>(load: impl FnOnce() -> T) -> (T, usize) {
+    let before = IN_USE.load(Ordering::SeqCst);
+    PEAK.store(before, Ordering::SeqCst);
+    let loaded = load();
+    (loaded, PEAK.load(Ordering::SeqCst) - before)
+}
+
+#[test]
+fn streaming_bounds_memory() {
+    // 64 MB as RGBA but only a few KB as a PNG
+    let path = std::env::temp_dir().join(format!("image_render_stream_{}.png", std::process::id()));
+    image::RgbImage::from_fn(4000, 4000, |x, y| image::Rgb([(x / 16) as u8, (y / 16) as u8, 128])).save(&path).unwrap();
+    let path = path.to_string_lossy().into_owned();
+    let timeout = Duration::from_secs(1);
+
+    let (streamed, streaming) = peak(|| load_image_streamed(&path, timeout, false, false, (40, 40)).unwrap());
+    assert_eq!(streamed.dimensions(), (40, 40));
+    // a little over the rows being shrunk, the decoder's buffers, and the 40x40 image
+    assert!(streaming < 4 << 20, "streaming used {} bytes", streaming);
+
+    let (full, decoding) = peak(|| load_image(&path, timeout, false, false).unwrap());
+    assert_eq!(full.dimensions(), (4000, 4000));
+    assert!(decoding > 40 << 20, "decoding used {} bytes", decoding);
+}
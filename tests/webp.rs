@@ -0,0 +1,35 @@
+use std::time::Duration;
+
+use image_render::load_image;
+
+// a 1x1 lossy WebP
+static WEBP: &[u8] = b"RIFF\x22\0\0\0WEBPVP8 \x16\0\0\0\x30\x01\0\x9d\x01\x2a\x01\0\x01\0\x0e\xc0\xfe\x25\xa4\0\x03\x70\0\0\0\0";
+
+// the WebP written to a file of its own since inputs are paths
+fn webp_file(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("image_render_{}_{}.webp", name, std::process::id()));
+    std::fs::write(&path, WEBP).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+#[cfg(feature = "webp")]
+#[test]
+fn webp_decodes_and_renders() {
+    use image::GenericImageView;
+    use image_render::{render_image, RenderOptions, Size};
+
+    let img = load_image(&webp_file("decodes"), Duration::from_secs(1), false, false).unwrap();
+    assert_eq!(img.dimensions(), (1, 1));
+
+    let mut out = Vec::new();
+    let options = RenderOptions { size: Size::Cells(2, 1), ..RenderOptions::default() };
+    render_image(&img, &options, &mut out).unwrap();
+    assert!(String::from_utf8(out).unwrap().starts_with("\x1b[48;2;"));
+}
+
+#[cfg(not(feature = "webp"))]
+#[test]
+fn webp_needs_its_feature() {
+    let e = load_image(&webp_file("needs_feature"), Duration::from_secs(1), false, false).unwrap_err();
+    assert!(e.contains("--features webp"), "{}", e);
+}